@@ -0,0 +1,28 @@
+-- Schema of the site as it was kept by hand in README.md before migrations,
+-- so that applying it to one of those databases changes nothing.
+
+CREATE TABLE IF NOT EXISTS books (
+id integer PRIMARY KEY AUTOINCREMENT,
+name text Not null,
+link text Not null,
+description text Not null);
+
+CREATE TABLE IF NOT EXISTS publications (
+id integer PRIMARY KEY AUTOINCREMENT,
+name text Not null,
+link text Not null,
+description text Not null);
+
+CREATE TABLE IF NOT EXISTS texts (
+id integer PRIMARY KEY AUTOINCREMENT,
+name text Not null,
+link text Not null,
+description text Not null);
+
+CREATE TABLE IF NOT EXISTS posts (
+id integer PRIMARY KEY AUTOINCREMENT ,
+title text Not null,
+lead text Not null,
+body text Not null,
+cover text Not null,
+created_at DATETIME DEFAULT CURRENT_TIMESTAMP);
@@ -0,0 +1 @@
+ALTER TABLE posts ADD COLUMN format text Not null DEFAULT 'html';
@@ -0,0 +1,12 @@
+CREATE TABLE sources (
+id integer PRIMARY KEY AUTOINCREMENT,
+post_id integer Not null REFERENCES posts(id) ON DELETE CASCADE,
+position integer Not null,
+author text Not null,
+title text Not null,
+year text Not null,
+archive text Not null,
+fond text Not null,
+opis text Not null,
+delo text Not null,
+url text Not null);
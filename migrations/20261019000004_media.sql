@@ -0,0 +1,9 @@
+CREATE TABLE media (
+id integer PRIMARY KEY AUTOINCREMENT,
+file_name text Not null UNIQUE,
+original_name text Not null,
+mime text Not null,
+size integer Not null,
+alt text Not null DEFAULT '',
+caption text Not null DEFAULT '',
+created_at DATETIME DEFAULT CURRENT_TIMESTAMP);
@@ -0,0 +1,5 @@
+CREATE TABLE users (
+id integer PRIMARY KEY AUTOINCREMENT,
+name text Not null UNIQUE,
+password_hash text Not null,
+created_at DATETIME DEFAULT CURRENT_TIMESTAMP);
@@ -0,0 +1,3 @@
+ALTER TABLE posts ADD COLUMN slug text Not null DEFAULT '';
+UPDATE posts SET slug = 'post-' || id;
+CREATE UNIQUE INDEX posts_slug ON posts (slug);
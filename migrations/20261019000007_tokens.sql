@@ -0,0 +1,9 @@
+CREATE TABLE tokens (
+id integer PRIMARY KEY AUTOINCREMENT,
+user_id integer Not null REFERENCES users (id) ON DELETE CASCADE,
+name text Not null,
+hash text Not null UNIQUE,
+scopes text Not null,
+expires_at DATETIME,
+last_used_at DATETIME,
+created_at DATETIME DEFAULT CURRENT_TIMESTAMP);
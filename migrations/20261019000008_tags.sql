@@ -0,0 +1,11 @@
+CREATE TABLE tags (
+id integer PRIMARY KEY AUTOINCREMENT,
+name text Not null UNIQUE,
+slug text Not null UNIQUE);
+
+CREATE TABLE post_tags (
+post_id integer Not null REFERENCES posts (id) ON DELETE CASCADE,
+tag_id integer Not null REFERENCES tags (id) ON DELETE CASCADE,
+PRIMARY KEY (post_id, tag_id));
+
+CREATE INDEX post_tags_tag ON post_tags (tag_id);
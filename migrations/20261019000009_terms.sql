@@ -0,0 +1,21 @@
+CREATE TABLE terms (
+id integer PRIMARY KEY AUTOINCREMENT,
+kind text Not null CHECK (kind IN ('period', 'region')),
+parent_id integer REFERENCES terms (id) ON DELETE CASCADE,
+name text Not null,
+slug text Not null UNIQUE);
+
+CREATE TABLE post_terms (
+post_id integer Not null REFERENCES posts (id) ON DELETE CASCADE,
+term_id integer Not null REFERENCES terms (id) ON DELETE CASCADE,
+PRIMARY KEY (post_id, term_id));
+
+CREATE TABLE book_terms (
+book_id integer Not null REFERENCES books (id) ON DELETE CASCADE,
+term_id integer Not null REFERENCES terms (id) ON DELETE CASCADE,
+PRIMARY KEY (book_id, term_id));
+
+CREATE TABLE publication_terms (
+publication_id integer Not null REFERENCES publications (id) ON DELETE CASCADE,
+term_id integer Not null REFERENCES terms (id) ON DELETE CASCADE,
+PRIMARY KEY (publication_id, term_id));
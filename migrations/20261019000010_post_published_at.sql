@@ -0,0 +1,7 @@
+-- SQLite can't add a column defaulting to CURRENT_TIMESTAMP, so new rows
+-- get their date from the application.
+ALTER TABLE posts ADD COLUMN published_at datetime Not null DEFAULT '1970-01-01 00:00:00';
+
+UPDATE posts SET published_at = created_at;
+
+CREATE INDEX posts_published_at ON posts (published_at);
@@ -0,0 +1,8 @@
+ALTER TABLE books ADD COLUMN position integer Not null DEFAULT 0;
+UPDATE books SET position = id;
+
+ALTER TABLE publications ADD COLUMN position integer Not null DEFAULT 0;
+UPDATE publications SET position = id;
+
+ALTER TABLE texts ADD COLUMN position integer Not null DEFAULT 0;
+UPDATE texts SET position = id;
@@ -0,0 +1,4 @@
+ALTER TABLE posts ADD COLUMN draft boolean Not null DEFAULT 0;
+ALTER TABLE books ADD COLUMN draft boolean Not null DEFAULT 0;
+ALTER TABLE publications ADD COLUMN draft boolean Not null DEFAULT 0;
+ALTER TABLE texts ADD COLUMN draft boolean Not null DEFAULT 0;
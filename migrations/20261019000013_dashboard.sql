@@ -0,0 +1,34 @@
+-- Kept by triggers, so edits made through the API, bulk actions and
+-- imports count as well. Catalog entries edited before this have no date.
+ALTER TABLE posts ADD COLUMN updated_at datetime;
+UPDATE posts SET updated_at = created_at;
+CREATE TRIGGER posts_inserted AFTER INSERT ON posts BEGIN
+    UPDATE posts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+CREATE TRIGGER posts_updated AFTER UPDATE OF title, lead, body, format, cover, slug, published_at, draft ON posts BEGIN
+    UPDATE posts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+ALTER TABLE books ADD COLUMN updated_at datetime;
+CREATE TRIGGER books_inserted AFTER INSERT ON books BEGIN
+    UPDATE books SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+CREATE TRIGGER books_updated AFTER UPDATE OF name, link, description, draft ON books BEGIN
+    UPDATE books SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+ALTER TABLE publications ADD COLUMN updated_at datetime;
+CREATE TRIGGER publications_inserted AFTER INSERT ON publications BEGIN
+    UPDATE publications SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+CREATE TRIGGER publications_updated AFTER UPDATE OF name, link, description, draft ON publications BEGIN
+    UPDATE publications SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+ALTER TABLE texts ADD COLUMN updated_at datetime;
+CREATE TRIGGER texts_inserted AFTER INSERT ON texts BEGIN
+    UPDATE texts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+CREATE TRIGGER texts_updated AFTER UPDATE OF name, link, description, draft ON texts BEGIN
+    UPDATE texts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
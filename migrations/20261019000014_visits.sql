@@ -0,0 +1,17 @@
+-- Daily totals per address; `kind` is 'page' or 'download'
+CREATE TABLE visits (
+day date Not null,
+kind text Not null,
+path text Not null,
+views integer Not null DEFAULT 0,
+visitors integer Not null DEFAULT 0,
+PRIMARY KEY (day, kind, path));
+
+-- Salted hashes of the visitors of the current day, only kept to count
+-- each of them once
+CREATE TABLE visitors (
+day date Not null,
+kind text Not null,
+path text Not null,
+hash text Not null,
+PRIMARY KEY (day, kind, path, hash));
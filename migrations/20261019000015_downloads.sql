@@ -0,0 +1,3 @@
+ALTER TABLE books ADD COLUMN downloads integer Not null DEFAULT 0;
+ALTER TABLE publications ADD COLUMN downloads integer Not null DEFAULT 0;
+ALTER TABLE texts ADD COLUMN downloads integer Not null DEFAULT 0;
@@ -0,0 +1,172 @@
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{NaiveDate, Utc};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqlitePool;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    models::visit::{self, Kind},
+    Auth, HistoryState,
+};
+
+// Page views and downloads are counted without cookies. Visitors are told
+// apart by a hash of their address and browser, salted with a random value
+// that is replaced every day and never stored, so nobody can be followed
+// from one day to the next. A restart also replaces the salt, and whoever
+// comes back later that day is counted again.
+pub struct Analytics {
+    salt: Mutex<(NaiveDate, [u8; 32])>,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for Analytics {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Analytics {
+    pub fn new(trusted_proxies: Vec<IpAddr>) -> Self {
+        Self {
+            salt: Mutex::new((Utc::now().date_naive(), rand::thread_rng().gen())),
+            trusted_proxies,
+        }
+    }
+
+    // Also tells whether the day has changed since the last visit
+    fn hash(&self, address: &str, agent: &str) -> (NaiveDate, String, bool) {
+        let today = Utc::now().date_naive();
+        let mut salt = self.salt.lock().unwrap();
+        let rotated = salt.0 != today;
+        if rotated {
+            *salt = (today, rand::thread_rng().gen());
+        }
+        let hash = Sha256::new()
+            .chain_update(salt.1)
+            .chain_update(address)
+            .chain_update([0])
+            .chain_update(agent)
+            .finalize();
+        (today, format!("{:x}", hash), rotated)
+    }
+
+    pub async fn record(
+        &self,
+        db: &SqlitePool,
+        kind: Kind,
+        path: &str,
+        headers: &HeaderMap,
+        peer: Option<SocketAddr>,
+    ) {
+        if is_bot(headers) {
+            return;
+        }
+        let (day, hash, rotated) = self.hash(&self.address(headers, peer), agent(headers));
+        if rotated && visit::forget_before(db, day).await.is_err() {
+            println!("Can't forget the visitors of past days");
+        }
+        if visit::record(db, day, kind, path, &hash).await.is_err() {
+            println!("Can't record a visit to {}", path);
+        }
+    }
+
+    // Only a proxy sets the headers, anyone else could send them to pass as
+    // many visitors. Requests on the Unix socket come without a peer and
+    // always through the proxy.
+    fn address(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> String {
+        let peer = peer.map(|peer| peer.ip());
+        let trusted = peer.is_none_or(|peer| self.trusted_proxies.contains(&peer));
+        // The proxy appends the address it saw to what the client sent
+        let forwarded = headers
+            .get("x-real-ip")
+            .or_else(|| headers.get("x-forwarded-for"))
+            .filter(|_| trusted)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .map(|value| value.trim().to_string());
+        forwarded
+            .or_else(|| peer.map(|peer| peer.to_string()))
+            .unwrap_or_default()
+    }
+}
+
+fn agent(headers: &HeaderMap) -> &str {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+}
+
+pub fn is_bot(headers: &HeaderMap) -> bool {
+    let agent = agent(headers).to_lowercase();
+    agent.is_empty()
+        || ["bot", "crawl", "spider", "slurp"]
+            .iter()
+            .any(|word| agent.contains(word))
+}
+
+// The pages of the site itself; feeds and downloads of sources are polled
+// by programs rather than read
+fn is_page(path: &str) -> bool {
+    let page =
+        path == "/" || path == "/blog" || path.starts_with("/blog/") || path.starts_with("/entry/");
+    page && !path.ends_with(".xml") && !path.ends_with(".bib")
+}
+
+// Successful views of the public pages, except the admin's own
+pub async fn track<B>(
+    State(state): State<Arc<HistoryState>>,
+    auth: Auth,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let tracked = request.method() == Method::GET
+        && auth.current_user.is_none()
+        && is_page(request.uri().path());
+    let path = request.uri().path().to_string();
+    let headers = request.headers().clone();
+    let response = next.run(request).await;
+    if tracked && response.status().is_success() {
+        tokio::spawn(async move {
+            let peer = peer.map(|ConnectInfo(peer)| peer);
+            state
+                .analytics
+                .record(&state.db, Kind::Page, &path, &headers, peer)
+                .await;
+        });
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn forwarded_address_is_only_believed_from_a_proxy() {
+        let analytics = Analytics::new(vec!["10.0.0.1".parse().unwrap()]);
+        let headers = forwarded("1.1.1.1, 2.2.2.2");
+        let client = "3.3.3.3:4000".parse().ok();
+        let proxy = "10.0.0.1:4000".parse().ok();
+        assert_eq!(analytics.address(&headers, client), "3.3.3.3");
+        assert_eq!(analytics.address(&headers, proxy), "2.2.2.2");
+        assert_eq!(analytics.address(&headers, None), "2.2.2.2");
+        assert_eq!(analytics.address(&HeaderMap::new(), proxy), "10.0.0.1");
+    }
+}
@@ -1,23 +1,12 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
 use axum_login::{secrecy::SecretVec, AuthUser};
+use rand::Rng;
+use sha2::{Digest, Sha256};
 
-#[derive(Debug, Clone)]
-pub struct User {
-    pub id: usize,
-    pub name: String,
-    pub password_hash: String,
-    pub role: Role,
-}
-
-impl User {
-    pub fn new(secret: String) -> Self {
-        Self {
-            id: 42,
-            name: "Admin".to_string(),
-            password_hash: secret,
-            role: Role::Admin,
-        }
-    }
-}
+use crate::models::user::User;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Role {
@@ -25,8 +14,10 @@ pub enum Role {
     Admin,
 }
 
-impl AuthUser<usize, Role> for User {
-    fn get_id(&self) -> usize {
+// The session keeps a digest of the hash, so changing the password signs
+// out every session of that user.
+impl AuthUser<i64, Role> for User {
+    fn get_id(&self) -> i64 {
         self.id
     }
 
@@ -38,3 +29,58 @@ impl AuthUser<usize, Role> for User {
         Some(self.role.clone())
     }
 }
+
+// What an API token may change
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Posts,
+    Catalog,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 2] = [Scope::Posts, Scope::Catalog];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Posts => "posts",
+            Scope::Catalog => "catalog",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Scope::Posts => "Школа",
+            Scope::Catalog => "Книги, публицистика, проза и поэзия",
+        }
+    }
+}
+
+const TOKEN_PREFIX: &str = "hist_";
+
+pub fn generate_token() -> String {
+    let bytes = rand::thread_rng().gen::<[u8; 32]>();
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}{}", TOKEN_PREFIX, hex)
+}
+
+// Tokens are long and random, so a fast hash is enough and lets them be
+// looked up by it.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("argon2 with default parameters can hash any password")
+        .to_string()
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
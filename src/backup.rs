@@ -0,0 +1,518 @@
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sqlx::{
+    query, query_as,
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
+    Connection,
+};
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fs::{self, File},
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{config::Backup, models::MIGRATOR, storage::Storage, HistoryError};
+
+// Every snapshot is a directory named after its creation time that holds
+// a copy of the database and a tarball of the uploads.
+pub const DATABASE: &str = "history.db";
+pub const UPLOADS: &str = "uploads.tar.gz";
+const NAME_FORMAT: &str = "%Y-%m-%dT%H-%M-%S%.3f";
+// Snapshots taken in the same millisecond get "_2", "_3" and so on
+const SUFFIX: char = '_';
+
+pub struct Snapshot {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub size: u64,
+}
+
+impl Snapshot {
+    pub fn size_kb(&self) -> u64 {
+        self.size.div_ceil(1024)
+    }
+}
+
+// `VACUUM INTO` copies a consistent snapshot while the site keeps serving
+// and writing to the database.
+pub async fn copy_database(db: &SqlitePool, path: &Path) -> Result<(), HistoryError> {
+    if path.exists() {
+        return Err(HistoryError::Invalid(format!(
+            "{} уже существует",
+            path.display()
+        )));
+    }
+    query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().to_string())
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// The snapshot is assembled under a temporary name, so a half-written one
+// never shows up in the list. Creating that directory is what claims the
+// name, so a scheduled run and the CLI can't both take it.
+pub async fn create(
+    db: &SqlitePool,
+    storage: &Storage,
+    dir: &Path,
+) -> Result<Snapshot, HistoryError> {
+    fs::create_dir_all(dir)?;
+    let stamp = Utc::now().format(NAME_FORMAT).to_string();
+    let mut number = 1;
+    let (name, temporary) = loop {
+        let name = match number {
+            1 => stamp.clone(),
+            number => format!("{}{}{}", stamp, SUFFIX, number),
+        };
+        let temporary = dir.join(format!(".{}", name));
+        if !dir.join(&name).exists() {
+            match fs::create_dir(&temporary) {
+                Ok(()) => break (name, temporary),
+                Err(error) if error.kind() != ErrorKind::AlreadyExists => return Err(error.into()),
+                Err(_) => {}
+            }
+        }
+        number += 1;
+    };
+    let path = dir.join(&name);
+    let written = write(db, storage, &temporary).await;
+    if written.is_err() {
+        let _ = fs::remove_dir_all(&temporary);
+    }
+    written?;
+    fs::rename(&temporary, &path)?;
+    fetch(dir, &name)
+}
+
+async fn write(db: &SqlitePool, storage: &Storage, path: &Path) -> Result<(), HistoryError> {
+    copy_database(db, &path.join(DATABASE)).await?;
+    let (root, target) = (storage.root().to_path_buf(), path.join(UPLOADS));
+    tokio::task::spawn_blocking(move || archive_uploads(&root, &target))
+        .await
+        .map_err(|_| HistoryError::InternalError)?
+}
+
+fn archive_uploads(root: &Path, target: &Path) -> Result<(), HistoryError> {
+    let mut tar = tar::Builder::new(GzEncoder::new(
+        File::create(target)?,
+        Compression::default(),
+    ));
+    if root.is_dir() {
+        for entry in fs::read_dir(root)? {
+            let entry = entry?;
+            let path = entry.path();
+            // Uploads still being written
+            if !entry.file_type()?.is_file() || path.extension().is_some_and(|ext| ext == "part") {
+                continue;
+            }
+            tar.append_path_with_name(&path, entry.file_name())?;
+        }
+    }
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+// Newest first.
+pub fn list(dir: &Path) -> Result<Vec<Snapshot>, HistoryError> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let stamp = match name.split_once(SUFFIX) {
+            None => name.as_str(),
+            Some((stamp, number)) if number.parse::<u32>().is_ok() => stamp,
+            Some(_) => continue,
+        };
+        let Ok(created_at) = NaiveDateTime::parse_from_str(stamp, NAME_FORMAT) else {
+            continue;
+        };
+        let mut size = 0;
+        for file in [DATABASE, UPLOADS] {
+            size += fs::metadata(entry.path().join(file)).map_or(0, |metadata| metadata.len());
+        }
+        snapshots.push(Snapshot {
+            name,
+            created_at: created_at.and_utc(),
+            size,
+        });
+    }
+    snapshots.sort_by_key(|snapshot| {
+        let number = snapshot
+            .name
+            .split_once(SUFFIX)
+            .map_or(1, |(_, number)| number.parse::<u32>().unwrap_or(1));
+        Reverse((snapshot.created_at, number))
+    });
+    Ok(snapshots)
+}
+
+// Only names of existing snapshots are accepted, which also keeps paths
+// built from user input inside the backup directory.
+pub fn fetch(dir: &Path, name: &str) -> Result<Snapshot, HistoryError> {
+    list(dir)?
+        .into_iter()
+        .find(|snapshot| snapshot.name == name)
+        .ok_or(HistoryError::NotFound)
+}
+
+pub fn file(dir: &Path, name: &str, file: &str) -> Result<PathBuf, HistoryError> {
+    if ![DATABASE, UPLOADS].contains(&file) {
+        return Err(HistoryError::NotFound);
+    }
+    let snapshot = fetch(dir, name)?;
+    Ok(dir.join(snapshot.name).join(file))
+}
+
+// Keeps the newest snapshot of each of the last `keep_daily` days,
+// `keep_weekly` weeks and `keep_monthly` months, and always the latest one.
+pub fn rotate(settings: &Backup) -> Result<usize, HistoryError> {
+    let snapshots = list(&settings.dir)?;
+    let mut keep = HashSet::new();
+    keep_newest(&snapshots, settings.keep_daily, &mut keep, |date| {
+        date.format("%Y-%m-%d").to_string()
+    });
+    keep_newest(&snapshots, settings.keep_weekly, &mut keep, |date| {
+        let week = date.iso_week();
+        format!("{}-{}", week.year(), week.week())
+    });
+    keep_newest(&snapshots, settings.keep_monthly, &mut keep, |date| {
+        date.format("%Y-%m").to_string()
+    });
+    if let Some(latest) = snapshots.first() {
+        keep.insert(latest.name.clone());
+    }
+    let mut removed = 0;
+    for snapshot in snapshots.iter() {
+        if !keep.contains(&snapshot.name) {
+            fs::remove_dir_all(settings.dir.join(&snapshot.name))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn keep_newest(
+    snapshots: &[Snapshot],
+    count: usize,
+    keep: &mut HashSet<String>,
+    period: impl Fn(&DateTime<Utc>) -> String,
+) {
+    let mut periods = HashSet::new();
+    for snapshot in snapshots.iter() {
+        if periods.len() == count {
+            break;
+        }
+        if periods.insert(period(&snapshot.created_at)) {
+            keep.insert(snapshot.name.clone());
+        }
+    }
+}
+
+// Replaces the content tables' rows with the snapshot's inside one transaction,
+// so the running site sees either the old content or the restored one.
+// The current state is saved as a snapshot first.
+pub async fn restore(
+    db: &SqlitePool,
+    storage: &Storage,
+    settings: &Backup,
+    name: &str,
+) -> Result<(), HistoryError> {
+    let source = file(&settings.dir, name, DATABASE)?;
+    let uploads = file(&settings.dir, name, UPLOADS)?;
+    let saved = create(db, storage, &settings.dir).await?;
+
+    // An older snapshot is brought to the current schema on a copy first,
+    // named after the snapshot just taken so that restores running at the
+    // same time don't share it
+    let copy = settings.dir.join(format!(".restore-{}.db", saved.name));
+    fs::copy(&source, &copy)?;
+    let restored = restore_database(db, &copy).await;
+    let _ = fs::remove_file(&copy);
+    restored?;
+
+    let storage = storage.clone();
+    tokio::task::spawn_blocking(move || unpack_uploads(&uploads, &storage))
+        .await
+        .map_err(|_| HistoryError::InternalError)?
+}
+
+async fn restore_database(db: &SqlitePool, copy: &Path) -> Result<(), HistoryError> {
+    let options = SqliteConnectOptions::new().filename(copy);
+    let snapshot = SqlitePoolOptions::new().connect_with(options).await?;
+    MIGRATOR.run(&snapshot).await.map_err(sqlx::Error::from)?;
+    snapshot.close().await;
+
+    // Accounts and tokens stay as they are now: an older snapshot would
+    // bring back old passwords and tokens revoked since
+    let tables: Vec<(String,)> = query_as(
+        "SELECT name FROM sqlite_master WHERE type = 'table' \
+         AND name NOT LIKE 'sqlite_%' \
+         AND name NOT IN ('_sqlx_migrations', 'users', 'tokens')",
+    )
+    .fetch_all(db)
+    .await?;
+    let mut connection = db.acquire().await?;
+    query("ATTACH DATABASE ? AS snapshot")
+        .bind(copy.to_string_lossy().to_string())
+        .execute(&mut connection)
+        .await?;
+    let copied = copy_tables(&mut connection, &tables).await;
+    query("DETACH DATABASE snapshot")
+        .execute(&mut connection)
+        .await?;
+    copied
+}
+
+async fn copy_tables(
+    connection: &mut sqlx::SqliteConnection,
+    tables: &[(String,)],
+) -> Result<(), HistoryError> {
+    let mut tx = connection.begin().await?;
+    // Rows of different tables reference each other while both are refilled
+    query("PRAGMA defer_foreign_keys = ON")
+        .execute(&mut tx)
+        .await?;
+    // Triggers would treat the copied rows as new ones, such as the ones
+    // that set `updated_at`; they are back before the transaction ends
+    let triggers: Vec<(String, String)> =
+        query_as("SELECT name, sql FROM main.sqlite_master WHERE type = 'trigger'")
+            .fetch_all(&mut tx)
+            .await?;
+    for (name, _) in triggers.iter() {
+        query(&format!("DROP TRIGGER main.\"{}\"", name))
+            .execute(&mut tx)
+            .await?;
+    }
+    for (table,) in tables.iter() {
+        query(&format!("DELETE FROM main.\"{}\"", table))
+            .execute(&mut tx)
+            .await?;
+    }
+    for (table,) in tables.iter() {
+        let columns: Vec<(String,)> =
+            query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+                .fetch_all(&mut tx)
+                .await?;
+        let columns = columns
+            .iter()
+            .map(|(column,)| format!("\"{}\"", column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        query(&format!(
+            "INSERT INTO main.\"{0}\" ({1}) SELECT {1} FROM snapshot.\"{0}\"",
+            table, columns
+        ))
+        .execute(&mut tx)
+        .await?;
+    }
+    for (_, sql) in triggers.iter() {
+        query(sql).execute(&mut tx).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+// Uploads are named by their content, so existing files are kept and the
+// ones the snapshot doesn't know are left for `check-uploads`.
+fn unpack_uploads(path: &Path, storage: &Storage) -> Result<(), HistoryError> {
+    let mut tar = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        if !entry.header().entry_type().is_file() || storage.path(&name).is_none() {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        storage.put(&name, &bytes)?;
+    }
+    Ok(())
+}
+
+// Waits for the interval to pass since the latest snapshot, so restarts
+// don't cause extra ones.
+pub fn schedule(db: SqlitePool, storage: Storage, settings: Backup) {
+    if settings.interval_hours == 0 {
+        return;
+    }
+    let interval = Duration::from_secs(settings.interval_hours * 60 * 60);
+    tokio::spawn(async move {
+        loop {
+            let age = list(&settings.dir)
+                .ok()
+                .and_then(|snapshots| snapshots.into_iter().next())
+                .and_then(|latest| (Utc::now() - latest.created_at).to_std().ok());
+            if let Some(age) = age {
+                tokio::time::sleep(interval.saturating_sub(age)).await;
+            }
+            match create(&db, &storage, &settings.dir).await {
+                Ok(snapshot) => println!("Backup {} created", snapshot.name),
+                Err(error) => {
+                    println!("Scheduled backup failed: {}", error.message());
+                    tokio::time::sleep(interval).await;
+                }
+            }
+            if rotate(&settings).is_err() {
+                println!("Can't remove old backups");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::Scope,
+        models::{self, token::Token, user::User},
+    };
+    use tempfile::TempDir;
+
+    async fn environment(dir: &TempDir) -> (SqlitePool, Storage, Backup) {
+        let db = models::connect(&dir.path().join("history.db"))
+            .await
+            .unwrap();
+        let storage = Storage::new(dir.path().join("uploads"));
+        fs::create_dir_all(storage.root()).unwrap();
+        let settings = Backup {
+            dir: dir.path().join("backups"),
+            ..Backup::default()
+        };
+        fs::create_dir_all(&settings.dir).unwrap();
+        (db, storage, settings)
+    }
+
+    async fn updated_at(db: &SqlitePool, table: &str) -> Option<String> {
+        sqlx::query_scalar(&format!("SELECT updated_at FROM {} WHERE id = 1", table))
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn snapshots_in_the_same_millisecond_get_their_own_names() {
+        let dir = TempDir::new().unwrap();
+        let (db, storage, settings) = environment(&dir).await;
+        let mut created = Vec::new();
+        for _ in 0..3 {
+            created.push(
+                create(&db, &storage, &settings.dir)
+                    .await
+                    .ok()
+                    .unwrap()
+                    .name,
+            );
+        }
+        let mut listed: Vec<String> = list(&settings.dir)
+            .ok()
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.name)
+            .collect();
+        listed.reverse();
+        assert_eq!(listed, created);
+    }
+
+    #[tokio::test]
+    async fn restore_keeps_edit_times() {
+        let dir = TempDir::new().unwrap();
+        let (db, storage, settings) = environment(&dir).await;
+        query(
+            "INSERT INTO posts (id, title, lead, body, cover, slug) \
+             VALUES (1, 'Заголовок', 'Лид', 'Текст', '', 'zagolovok')",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        query("UPDATE posts SET updated_at = '2020-01-02 03:04:05'")
+            .execute(&db)
+            .await
+            .unwrap();
+        // Entries edited before edits were tracked have no time at all
+        query("INSERT INTO books (id, name, link, description) VALUES (1, 'Книга', '', '')")
+            .execute(&db)
+            .await
+            .unwrap();
+        query("UPDATE books SET updated_at = NULL")
+            .execute(&db)
+            .await
+            .unwrap();
+        let snapshot = create(&db, &storage, &settings.dir).await.ok().unwrap();
+
+        assert!(restore(&db, &storage, &settings, &snapshot.name)
+            .await
+            .is_ok());
+
+        assert_eq!(
+            updated_at(&db, "posts").await.as_deref(),
+            Some("2020-01-02 03:04:05")
+        );
+        assert_eq!(updated_at(&db, "books").await, None);
+        // The triggers still work for edits made after the restore
+        query("UPDATE posts SET title = 'Другой'")
+            .execute(&db)
+            .await
+            .unwrap();
+        assert_ne!(
+            updated_at(&db, "posts").await.as_deref(),
+            Some("2020-01-02 03:04:05")
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_keeps_accounts_and_tokens() {
+        let dir = TempDir::new().unwrap();
+        let (db, storage, settings) = environment(&dir).await;
+        User::create(&db, "admin", "old hash").await.unwrap();
+        let user = User::fetch_by_name(&db, "admin").await.unwrap();
+        Token::create(
+            &db,
+            user.id,
+            "revoked",
+            "revoked hash",
+            &[Scope::Posts],
+            None,
+        )
+        .await
+        .unwrap();
+        let snapshot = create(&db, &storage, &settings.dir).await.ok().unwrap();
+        User::set_password(&db, "admin", "new hash").await.unwrap();
+        for token in Token::list(&db, user.id).await.unwrap() {
+            Token::delete(&db, token.id, user.id).await.unwrap();
+        }
+        Token::create(
+            &db,
+            user.id,
+            "current",
+            "current hash",
+            &[Scope::Posts],
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(restore(&db, &storage, &settings, &snapshot.name)
+            .await
+            .is_ok());
+
+        let user = User::fetch_by_name(&db, "admin").await.unwrap();
+        assert_eq!(user.password_hash, "new hash");
+        let names: Vec<String> = Token::list(&db, user.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|token| token.name)
+            .collect();
+        assert_eq!(names, ["current"]);
+    }
+}
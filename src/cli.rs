@@ -0,0 +1,305 @@
+use clap::{Parser, Subcommand};
+use sqlx::sqlite::SqlitePool;
+use std::{
+    fs::{read, write, File},
+    io::{stdin, Cursor, IsTerminal},
+    path::PathBuf,
+    process,
+};
+
+use crate::{
+    auth, backup,
+    config::{Config, Overrides},
+    consistency, models,
+    models::user::User,
+    storage::Storage,
+    transfer::{self, Export},
+    HistoryError,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Сайт shubinav.ru и его обслуживание")]
+pub struct Cli {
+    #[command(flatten)]
+    pub overrides: Overrides,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the web server, the default
+    Serve,
+    /// Bring the database schema up to date and list applied migrations
+    Migrate,
+    /// Manage the accounts that can sign in to the admin
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Write all content to a versioned JSON file, or to a zip archive
+    /// together with the uploaded files when the name ends in .zip
+    Export {
+        /// Where to write, standard output by default
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Load content from a JSON export or an archive, updating entries that
+    /// already exist
+    Import { file: PathBuf },
+    /// Snapshot the database and uploads while the site is running
+    #[command(subcommand)]
+    Backup(BackupCommand),
+    /// Compare uploaded files with the database
+    CheckUploads {
+        /// Remove orphaned files and rebuild missing image variants
+        #[arg(long)]
+        cleanup: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// Take a snapshot now and remove the ones the retention doesn't keep
+    Create,
+    List,
+    /// Replace the content and add missing uploads from a snapshot, after
+    /// taking a snapshot of the current state
+    Restore {
+        name: String,
+    },
+    /// Only copy the database to the given file
+    Database {
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    List,
+    /// Create an account, the password is read from the terminal or stdin
+    Add {
+        name: String,
+    },
+    /// Change the password of an account
+    Passwd {
+        name: String,
+    },
+    Remove {
+        name: String,
+    },
+}
+
+// Everything but `serve`, which main runs itself.
+pub async fn run(command: Command, config: &Config, db: &SqlitePool, storage: &Storage) {
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Migrate => migrate(db).await,
+        Command::User(command) => user(command, db).await,
+        Command::Export { output } => export(db, storage, output).await,
+        Command::Import { file } => import(db, storage, file).await,
+        Command::Backup(command) => backups(command, config, db, storage).await,
+        Command::CheckUploads { cleanup } => check_uploads(db, storage, cleanup).await,
+    }
+}
+
+fn fail(message: &str) -> ! {
+    println!("{}", message);
+    process::exit(1);
+}
+
+// Migrations run whenever the database is opened, so this only reports.
+async fn migrate(db: &SqlitePool) {
+    let applied = models::applied_migrations(db)
+        .await
+        .unwrap_or_else(|_| fail("Can't read applied migrations"));
+    for (version, description) in applied {
+        println!("{}  {}", version, description);
+    }
+    println!("Database is up to date");
+}
+
+async fn user(command: UserCommand, db: &SqlitePool) {
+    match command {
+        UserCommand::List => {
+            let users = User::list(db)
+                .await
+                .unwrap_or_else(|_| fail("Can't read users"));
+            for user in users {
+                println!("{}", user.name);
+            }
+        }
+        UserCommand::Add { name } => {
+            if User::fetch_by_name(db, &name).await.is_ok() {
+                fail(&format!("User {} already exists", name));
+            }
+            let password_hash = auth::hash_password(&read_password());
+            User::create(db, &name, &password_hash)
+                .await
+                .unwrap_or_else(|_| fail("Can't create user"));
+            println!("User {} created", name);
+        }
+        UserCommand::Passwd { name } => {
+            if User::fetch_by_name(db, &name).await.is_err() {
+                fail(&format!("No user {}", name));
+            }
+            let password_hash = auth::hash_password(&read_password());
+            User::set_password(db, &name, &password_hash)
+                .await
+                .unwrap_or_else(|_| fail("Can't change password"));
+            println!(
+                "Password of {} changed, their sessions are signed out",
+                name
+            );
+        }
+        UserCommand::Remove { name } => {
+            if User::fetch_by_name(db, &name).await.is_err() {
+                fail(&format!("No user {}", name));
+            }
+            if User::count(db).await.unwrap_or(0) <= 1 {
+                fail("Can't remove the last user, nobody could sign in");
+            }
+            User::delete(db, &name)
+                .await
+                .unwrap_or_else(|_| fail("Can't remove user"));
+            println!("User {} removed", name);
+        }
+    }
+}
+
+async fn backups(command: BackupCommand, config: &Config, db: &SqlitePool, storage: &Storage) {
+    let settings = &config.backup;
+    match command {
+        BackupCommand::Create => {
+            let snapshot = backup::create(db, storage, &settings.dir)
+                .await
+                .unwrap_or_else(|error| fail(&format!("Backup failed: {}", error.message())));
+            println!(
+                "Snapshot {} created ({} Kb)",
+                snapshot.name,
+                snapshot.size_kb()
+            );
+            match backup::rotate(settings) {
+                Ok(removed) if removed > 0 => println!("{} old snapshots removed", removed),
+                Ok(_) => {}
+                Err(_) => fail("Can't remove old snapshots"),
+            }
+        }
+        BackupCommand::List => {
+            let snapshots = backup::list(&settings.dir)
+                .unwrap_or_else(|_| fail(&format!("Can't read {}", settings.dir.display())));
+            for snapshot in snapshots {
+                println!("{}  {} Kb", snapshot.name, snapshot.size_kb());
+            }
+        }
+        BackupCommand::Restore { name } => {
+            match backup::restore(db, storage, settings, &name).await {
+                Ok(()) => println!("Snapshot {} restored", name),
+                Err(HistoryError::NotFound) => fail(&format!("No snapshot {}", name)),
+                Err(error) => fail(&format!("Restore failed: {}", error.message())),
+            }
+        }
+        BackupCommand::Database { output } => match backup::copy_database(db, &output).await {
+            Ok(()) => println!("Database copied to {}", output.display()),
+            Err(error) => fail(&format!("Backup failed: {}", error.message())),
+        },
+    }
+}
+
+// Prompts twice on a terminal; a password piped in is taken as is, which
+// keeps the command usable from scripts.
+fn read_password() -> String {
+    let password = if stdin().is_terminal() {
+        let password = rpassword::prompt_password("Password: ")
+            .unwrap_or_else(|_| fail("Can't read password"));
+        let repeated = rpassword::prompt_password("Repeat password: ")
+            .unwrap_or_else(|_| fail("Can't read password"));
+        if password != repeated {
+            fail("Passwords don't match");
+        }
+        password
+    } else {
+        let mut line = String::new();
+        stdin()
+            .read_line(&mut line)
+            .unwrap_or_else(|_| fail("Can't read password"));
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if password.is_empty() {
+        fail("Password can't be empty");
+    }
+    password
+}
+
+async fn export(db: &SqlitePool, storage: &Storage, output: Option<PathBuf>) {
+    let export = transfer::export(db)
+        .await
+        .unwrap_or_else(|_| fail("Can't read content"));
+    let json = serde_json::to_string_pretty(&export).unwrap_or_else(|_| fail("Can't export"));
+    match output {
+        Some(path) if path.extension().is_some_and(|extension| extension == "zip") => {
+            let file = File::create(&path)
+                .unwrap_or_else(|error| fail(&format!("{}: {}", path.display(), error)));
+            if let Err(error) = transfer::write_archive(file, &export, storage) {
+                fail(&format!("Export failed: {}", error.message()));
+            }
+            println!("Content and uploads exported to {}", path.display());
+        }
+        Some(path) => {
+            write(&path, json)
+                .unwrap_or_else(|error| fail(&format!("{}: {}", path.display(), error)));
+            println!("Content exported to {}", path.display());
+        }
+        None => println!("{}", json),
+    }
+}
+
+async fn import(db: &SqlitePool, storage: &Storage, file: PathBuf) {
+    let bytes = read(&file).unwrap_or_else(|error| fail(&format!("{}: {}", file.display(), error)));
+    let (export, uploads) = if transfer::is_archive(&bytes) {
+        let (export, uploads) = transfer::read_archive(Cursor::new(bytes), storage)
+            .unwrap_or_else(|error| fail(&format!("{}: {}", file.display(), error.message())));
+        (export, Some(uploads))
+    } else {
+        let export: Export = serde_json::from_slice(&bytes)
+            .unwrap_or_else(|error| fail(&format!("{}: {}", file.display(), error)));
+        (export, None)
+    };
+    let imported = transfer::import(db, export)
+        .await
+        .unwrap_or_else(|error| fail(&format!("Import failed: {}", error.message())));
+    if let Some(uploads) = uploads {
+        uploads
+            .restore(storage)
+            .unwrap_or_else(|error| fail(&format!("Import failed: {}", error.message())));
+    }
+    println!(
+        "Imported {} books, {} publications, {} texts, {} posts and {} media entries",
+        imported.books, imported.publications, imported.texts, imported.posts, imported.media
+    );
+}
+
+async fn check_uploads(db: &SqlitePool, storage: &Storage, cleanup: bool) {
+    let report = consistency::check(db, storage)
+        .await
+        .unwrap_or_else(|_| fail(&format!("Can't read {}", storage.root().display())));
+    for missing in report.missing.iter() {
+        println!("missing  {}  ({})", missing.file_name, missing.owner);
+    }
+    for orphan in report.orphaned.iter() {
+        println!("orphaned {}  ({} bytes)", orphan.file_name, orphan.size);
+    }
+    println!(
+        "{} referenced files checked: {} missing, {} orphaned ({} Kb)",
+        report.checked,
+        report.missing.len(),
+        report.orphaned.len(),
+        report.orphaned_kb()
+    );
+    if cleanup {
+        match consistency::cleanup(storage, &report).await {
+            Ok(fixed) => println!("{} files cleaned up", fixed),
+            Err(_) => fail("Cleanup failed"),
+        }
+    } else if !report.is_clean() {
+        println!("Dry run, pass --cleanup to remove orphaned files and rebuild variants");
+    }
+}
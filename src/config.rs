@@ -0,0 +1,390 @@
+use axum::http::HeaderValue;
+use chrono::FixedOffset;
+use clap::Args;
+use serde::Deserialize;
+use std::{
+    fs::read_to_string,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_FILE: &str = "history.toml";
+const MB: usize = 1024 * 1024;
+// Far above any sensible value, low enough that the byte and second counts
+// made from them fit
+const MAX_BODY_LIMIT_MB: usize = 1024;
+const MAX_SESSION_HOURS: u64 = 24 * 366;
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Site {
+    pub title: String,
+    pub description: String,
+    // Absolute address of the site, used where relative links won't do
+    pub url: String,
+    // Shows next to the download links how often they were followed
+    pub show_downloads: bool,
+    // Offset from UTC in which editors see and type publication dates
+    pub utc_offset: String,
+}
+
+impl Default for Site {
+    fn default() -> Self {
+        Self {
+            title: "Shubinav.ru".to_string(),
+            description: String::new(),
+            url: String::new(),
+            show_downloads: false,
+            utc_offset: "+03:00".to_string(),
+        }
+    }
+}
+
+impl Site {
+    // Checked by validate, so the fallback is never reached in practice
+    pub fn offset(&self) -> FixedOffset {
+        self.utc_offset
+            .parse()
+            .unwrap_or_else(|_| FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Backup {
+    pub dir: PathBuf,
+    // Zero turns scheduled backups off
+    pub interval_hours: u64,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+}
+
+impl Default for Backup {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("backups"),
+            interval_hours: 24,
+            keep_daily: 7,
+            keep_weekly: 4,
+            keep_monthly: 6,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Api {
+    // Sites whose pages may read the API, "*" for any
+    pub cors_origins: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    // When set the server listens on this Unix socket instead of `bind`
+    pub socket: Option<PathBuf>,
+    pub database: PathBuf,
+    pub uploads: PathBuf,
+    #[serde(rename = "static")]
+    pub static_dir: PathBuf,
+    pub body_limit_mb: usize,
+    pub session_hours: u64,
+    // Proxies whose X-Forwarded-For and X-Real-IP headers are believed;
+    // behind a Unix socket the headers always are
+    pub trusted_proxies: Vec<IpAddr>,
+    pub site: Site,
+    pub backup: Backup,
+    pub api: Api,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:8888".to_string(),
+            socket: None,
+            database: PathBuf::from("db/history.db"),
+            uploads: PathBuf::from("static/uploads"),
+            static_dir: PathBuf::from("static"),
+            body_limit_mb: 32,
+            session_hours: 24,
+            trusted_proxies: Vec::new(),
+            site: Site::default(),
+            backup: Backup::default(),
+            api: Api::default(),
+        }
+    }
+}
+
+// Every setting can also be given on the command line or in a HISTORY_*
+// variable; both take precedence over the configuration file.
+#[derive(Args, Default)]
+pub struct Overrides {
+    /// Configuration file, history.toml in the working directory by default
+    #[arg(long, env = "HISTORY_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+    /// Address and port to listen on
+    #[arg(long, env = "HISTORY_BIND", global = true)]
+    pub bind: Option<String>,
+    /// Unix socket to listen on instead of the address
+    #[arg(long, env = "HISTORY_SOCKET", global = true)]
+    pub socket: Option<PathBuf>,
+    /// SQLite database file
+    #[arg(long, env = "HISTORY_DATABASE", global = true)]
+    pub database: Option<PathBuf>,
+    /// Directory for uploaded files
+    #[arg(long, env = "HISTORY_UPLOADS", global = true)]
+    pub uploads: Option<PathBuf>,
+    /// Directory served under /static
+    #[arg(long = "static", env = "HISTORY_STATIC", global = true)]
+    pub static_dir: Option<PathBuf>,
+    /// Largest accepted request body, in megabytes
+    #[arg(long, env = "HISTORY_BODY_LIMIT_MB", global = true)]
+    pub body_limit_mb: Option<usize>,
+    /// How long an admin session lasts, in hours
+    #[arg(long, env = "HISTORY_SESSION_HOURS", global = true)]
+    pub session_hours: Option<u64>,
+    /// Comma-separated addresses of proxies trusted to pass the client's address
+    #[arg(
+        long,
+        env = "HISTORY_TRUSTED_PROXIES",
+        global = true,
+        value_delimiter = ','
+    )]
+    pub trusted_proxies: Option<Vec<IpAddr>>,
+    #[arg(long, env = "HISTORY_SITE_TITLE", global = true)]
+    pub site_title: Option<String>,
+    #[arg(long, env = "HISTORY_SITE_DESCRIPTION", global = true)]
+    pub site_description: Option<String>,
+    #[arg(long, env = "HISTORY_SITE_URL", global = true)]
+    pub site_url: Option<String>,
+    /// Show download counts on the home page
+    #[arg(long, env = "HISTORY_SITE_SHOW_DOWNLOADS", global = true)]
+    pub site_show_downloads: Option<bool>,
+    /// Offset from UTC for publication dates in the admin, e.g. +03:00
+    #[arg(long, env = "HISTORY_SITE_UTC_OFFSET", global = true)]
+    pub site_utc_offset: Option<String>,
+    /// Directory for database and uploads snapshots
+    #[arg(long, env = "HISTORY_BACKUP_DIR", global = true)]
+    pub backup_dir: Option<PathBuf>,
+    /// Hours between scheduled snapshots, 0 to turn them off
+    #[arg(long, env = "HISTORY_BACKUP_INTERVAL_HOURS", global = true)]
+    pub backup_interval_hours: Option<u64>,
+    /// How many days keep their newest snapshot
+    #[arg(long, env = "HISTORY_BACKUP_KEEP_DAILY", global = true)]
+    pub backup_keep_daily: Option<usize>,
+    /// How many weeks keep their newest snapshot
+    #[arg(long, env = "HISTORY_BACKUP_KEEP_WEEKLY", global = true)]
+    pub backup_keep_weekly: Option<usize>,
+    /// How many months keep their newest snapshot
+    #[arg(long, env = "HISTORY_BACKUP_KEEP_MONTHLY", global = true)]
+    pub backup_keep_monthly: Option<usize>,
+    /// Comma-separated origins allowed to read the API, "*" for any
+    #[arg(
+        long,
+        env = "HISTORY_API_CORS_ORIGINS",
+        global = true,
+        value_delimiter = ','
+    )]
+    pub api_cors_origins: Option<Vec<String>>,
+}
+
+impl Config {
+    // Defaults, then the file, then the overrides; all problems are
+    // collected so that they can be fixed in one go. Settings only the
+    // server uses are checked when `serving`.
+    pub fn load(overrides: Overrides, serving: bool) -> Result<Self, Vec<String>> {
+        let mut config = match &overrides.config {
+            Some(path) => Self::read(path)?,
+            None if Path::new(DEFAULT_FILE).exists() => Self::read(Path::new(DEFAULT_FILE))?,
+            None => Self::default(),
+        };
+        config.apply(overrides);
+        config.validate(serving)?;
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Self, Vec<String>> {
+        let text =
+            read_to_string(path).map_err(|error| vec![format!("{}: {}", path.display(), error)])?;
+        toml::from_str(&text).map_err(|error| vec![format!("{}: {}", path.display(), error)])
+    }
+
+    fn apply(&mut self, overrides: Overrides) {
+        if let Some(bind) = overrides.bind {
+            self.bind = bind;
+        }
+        if let Some(socket) = overrides.socket {
+            self.socket = Some(socket);
+        }
+        if let Some(database) = overrides.database {
+            self.database = database;
+        }
+        if let Some(uploads) = overrides.uploads {
+            self.uploads = uploads;
+        }
+        if let Some(static_dir) = overrides.static_dir {
+            self.static_dir = static_dir;
+        }
+        if let Some(body_limit_mb) = overrides.body_limit_mb {
+            self.body_limit_mb = body_limit_mb;
+        }
+        if let Some(session_hours) = overrides.session_hours {
+            self.session_hours = session_hours;
+        }
+        if let Some(trusted_proxies) = overrides.trusted_proxies {
+            self.trusted_proxies = trusted_proxies;
+        }
+        if let Some(title) = overrides.site_title {
+            self.site.title = title;
+        }
+        if let Some(description) = overrides.site_description {
+            self.site.description = description;
+        }
+        if let Some(url) = overrides.site_url {
+            self.site.url = url;
+        }
+        if let Some(show_downloads) = overrides.site_show_downloads {
+            self.site.show_downloads = show_downloads;
+        }
+        if let Some(utc_offset) = overrides.site_utc_offset {
+            self.site.utc_offset = utc_offset;
+        }
+        if let Some(dir) = overrides.backup_dir {
+            self.backup.dir = dir;
+        }
+        if let Some(interval_hours) = overrides.backup_interval_hours {
+            self.backup.interval_hours = interval_hours;
+        }
+        if let Some(keep_daily) = overrides.backup_keep_daily {
+            self.backup.keep_daily = keep_daily;
+        }
+        if let Some(keep_weekly) = overrides.backup_keep_weekly {
+            self.backup.keep_weekly = keep_weekly;
+        }
+        if let Some(keep_monthly) = overrides.backup_keep_monthly {
+            self.backup.keep_monthly = keep_monthly;
+        }
+        if let Some(cors_origins) = overrides.api_cors_origins {
+            self.api.cors_origins = cors_origins;
+        }
+    }
+
+    fn validate(&mut self, serving: bool) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        if self.socket.is_none() && self.bind.parse::<SocketAddr>().is_err() {
+            problems.push(format!(
+                "bind: \"{}\" is not an address with a port, e.g. 127.0.0.1:8888",
+                self.bind
+            ));
+        }
+        if self.database.as_os_str().is_empty() {
+            problems.push("database: path is empty".to_string());
+        }
+        if self.uploads.exists() && !self.uploads.is_dir() {
+            problems.push(format!(
+                "uploads: {} is not a directory",
+                self.uploads.display()
+            ));
+        }
+        if serving && !self.static_dir.is_dir() {
+            problems.push(format!(
+                "static: {} is not a directory",
+                self.static_dir.display()
+            ));
+        }
+        if !(1..=MAX_BODY_LIMIT_MB).contains(&self.body_limit_mb) {
+            problems.push(format!(
+                "body_limit_mb: must be between 1 and {}",
+                MAX_BODY_LIMIT_MB
+            ));
+        }
+        if !(1..=MAX_SESSION_HOURS).contains(&self.session_hours) {
+            problems.push(format!(
+                "session_hours: must be between 1 and {}",
+                MAX_SESSION_HOURS
+            ));
+        }
+        let url = &self.site.url;
+        if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+            problems.push(format!(
+                "site.url: \"{}\" must start with http:// or https://",
+                self.site.url
+            ));
+        }
+        self.site.url = self.site.url.trim_end_matches('/').to_string();
+        if self.site.utc_offset.parse::<FixedOffset>().is_err() {
+            problems.push(format!(
+                "site.utc_offset: \"{}\" is not an offset, e.g. +03:00",
+                self.site.utc_offset
+            ));
+        }
+        if self.backup.dir.exists() && !self.backup.dir.is_dir() {
+            problems.push(format!(
+                "backup.dir: {} is not a directory",
+                self.backup.dir.display()
+            ));
+        }
+        if self.backup.keep_daily + self.backup.keep_weekly + self.backup.keep_monthly == 0 {
+            problems.push(
+                "backup: at least one of keep_daily, keep_weekly and keep_monthly must be set"
+                    .to_string(),
+            );
+        }
+        for origin in self.api.cors_origins.iter() {
+            let valid = origin == "*"
+                || (origin.starts_with("http://") || origin.starts_with("https://"))
+                    && !origin
+                        .trim_end_matches('/')
+                        .contains(|c: char| c.is_whitespace())
+                    && origin.parse::<HeaderValue>().is_ok();
+            if !valid {
+                problems.push(format!(
+                    "api.cors_origins: \"{}\" is not an origin, e.g. https://example.com",
+                    origin
+                ));
+            }
+        }
+        self.api.cors_origins = self
+            .api
+            .cors_origins
+            .iter()
+            .map(|origin| origin.trim_end_matches('/').to_string())
+            .collect();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn body_limit(&self) -> usize {
+        self.body_limit_mb * MB
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_that_would_overflow_are_reported_together() {
+        let mut config = Config {
+            body_limit_mb: usize::MAX / 1024,
+            session_hours: u64::MAX / 60,
+            ..Config::default()
+        };
+        let problems = config.validate(false).err().unwrap();
+        assert_eq!(
+            problems,
+            vec![
+                "body_limit_mb: must be between 1 and 1024",
+                "session_hours: must be between 1 and 8784",
+            ]
+        );
+        config.body_limit_mb = MAX_BODY_LIMIT_MB;
+        config.session_hours = MAX_SESSION_HOURS;
+        assert!(config.validate(false).is_ok());
+    }
+}
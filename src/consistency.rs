@@ -0,0 +1,290 @@
+use sqlx::sqlite::SqlitePool;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_dir,
+    io::ErrorKind,
+};
+
+use crate::{
+    config::Site,
+    images::{self, VARIANTS},
+    models::{
+        media::Media,
+        overview::{self, Item},
+    },
+    storage::Storage,
+    HistoryError, Post,
+};
+
+pub struct Missing {
+    pub file_name: String,
+    pub owner: String,
+    // Set when the missing file is a variant that can be rebuilt from it
+    pub original: Option<String>,
+}
+
+pub struct Orphan {
+    pub file_name: String,
+    pub size: u64,
+}
+
+pub struct Report {
+    pub checked: usize,
+    pub missing: Vec<Missing>,
+    pub orphaned: Vec<Orphan>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty()
+    }
+
+    pub fn orphaned_kb(&self) -> u64 {
+        self.orphaned
+            .iter()
+            .map(|orphan| orphan.size)
+            .sum::<u64>()
+            .div_ceil(1024)
+    }
+}
+
+struct Reference {
+    owner: String,
+    original: Option<String>,
+}
+
+// Every file the database points at, with a human readable owner.
+async fn referenced(db: &SqlitePool) -> Result<HashMap<String, Reference>, HistoryError> {
+    let mut files = HashMap::new();
+    for post in Post::list(db).await? {
+        if !post.cover.is_empty() {
+            with_variants(&mut files, &post.cover, format!("Обложка «{}»", post.title));
+        }
+    }
+    for media in Media::list(db, "").await? {
+        let owner = format!("Медиатека: {}", media.original_name);
+        if media.has_variants() {
+            with_variants(&mut files, &media.file_name, owner);
+        } else {
+            files.insert(
+                media.file_name,
+                Reference {
+                    owner,
+                    original: None,
+                },
+            );
+        }
+    }
+    Ok(files)
+}
+
+fn with_variants(files: &mut HashMap<String, Reference>, file_name: &str, owner: String) {
+    for variant in VARIANTS.iter() {
+        for extension in ["jpg", "webp"] {
+            files.insert(
+                images::variant_name(file_name, variant.name, extension),
+                Reference {
+                    owner: owner.clone(),
+                    original: Some(file_name.to_string()),
+                },
+            );
+        }
+    }
+    files.insert(
+        file_name.to_string(),
+        Reference {
+            owner,
+            original: None,
+        },
+    );
+}
+
+pub async fn check(db: &SqlitePool, storage: &Storage) -> Result<Report, HistoryError> {
+    let mut referenced = referenced(db).await?;
+    let checked = referenced.len();
+    let mut orphaned = Vec::new();
+    // Nothing has been uploaded yet, so every reference is missing
+    let entries = match read_dir(storage.root()) {
+        Err(error) if error.kind() == ErrorKind::NotFound => Vec::new(),
+        entries => entries?.collect(),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if referenced.remove(&file_name).is_none() {
+            orphaned.push(Orphan {
+                file_name,
+                size: entry.metadata()?.len(),
+            });
+        }
+    }
+    orphaned.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    let mut missing: Vec<Missing> = referenced
+        .into_iter()
+        .map(|(file_name, reference)| Missing {
+            file_name,
+            owner: reference.owner,
+            original: reference.original,
+        })
+        .collect();
+    missing.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    Ok(Report {
+        checked,
+        missing,
+        orphaned,
+    })
+}
+
+// Removes orphaned files and rebuilds image variants whose original is
+// still there. Files that are referenced but gone can only be reported.
+pub async fn cleanup(storage: &Storage, report: &Report) -> Result<usize, HistoryError> {
+    let mut fixed = 0;
+    for orphan in report.orphaned.iter() {
+        storage.remove(&orphan.file_name)?;
+        fixed += 1;
+    }
+    let mut originals: Vec<String> = report
+        .missing
+        .iter()
+        .filter_map(|missing| missing.original.clone())
+        .filter(|original| storage.exists(original))
+        .collect();
+    originals.sort();
+    originals.dedup();
+    for original in originals {
+        let storage = storage.clone();
+        let rebuilt = tokio::task::spawn_blocking(move || images::rebuild(&storage, &original))
+            .await
+            .map_err(|_| HistoryError::InternalError)?;
+        if rebuilt.is_ok() {
+            fixed += 1;
+        }
+    }
+    Ok(fixed)
+}
+
+pub struct Usage {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Usage {
+    pub fn mb(&self) -> String {
+        format!("{:.1}", self.bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+pub fn usage(storage: &Storage) -> Result<Usage, HistoryError> {
+    let mut usage = Usage { files: 0, bytes: 0 };
+    let entries = match read_dir(storage.root()) {
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(usage),
+        entries => entries?,
+    };
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            usage.files += 1;
+            usage.bytes += entry.metadata()?.len();
+        }
+    }
+    Ok(usage)
+}
+
+pub struct BrokenLink {
+    pub item: Item,
+    pub link: String,
+    pub problem: &'static str,
+}
+
+const UPLOADS: &str = "/static/uploads/";
+
+// Only links within the site are checked: uploads that are gone and posts
+// that are gone or hidden. External links are not requested.
+pub async fn links(
+    db: &SqlitePool,
+    storage: &Storage,
+    site: &Site,
+) -> Result<Vec<BrokenLink>, HistoryError> {
+    let posts = Post::list(db).await?;
+    let published: HashSet<u32> = posts
+        .iter()
+        .filter(|post| !post.draft)
+        .map(|post| post.id)
+        .collect();
+    let mut broken = Vec::new();
+    for (item, link) in overview::catalog_links(db).await? {
+        let local = match link.strip_prefix(site.url.trim_end_matches('/')) {
+            Some(path) => Some(path),
+            None => (link.starts_with('/') && !link.starts_with("//")).then_some(link.as_str()),
+        };
+        let problem = if link.trim().is_empty() {
+            Some("Ссылка не указана")
+        } else {
+            local.and_then(|path| problem(path, storage, &published))
+        };
+        if let Some(problem) = problem {
+            broken.push(BrokenLink {
+                item,
+                link,
+                problem,
+            });
+        }
+    }
+    for post in posts {
+        let mut links: Vec<&str> = local_links(&post.body, site).collect();
+        links.sort_unstable();
+        links.dedup();
+        for link in links {
+            if let Some(problem) = problem(link, storage, &published) {
+                broken.push(BrokenLink {
+                    item: Item {
+                        table: "posts".to_string(),
+                        id: post.id,
+                        title: post.title.clone(),
+                        updated_at: None,
+                    },
+                    link: link.to_string(),
+                    problem,
+                });
+            }
+        }
+    }
+    Ok(broken)
+}
+
+// Links within the site in a text, both in HTML attributes and in
+// Markdown, as paths
+fn local_links<'a>(body: &'a str, site: &'a Site) -> impl Iterator<Item = &'a str> {
+    let url = site.url.trim_end_matches('/');
+    [UPLOADS, "/entry/"].into_iter().flat_map(move |prefix| {
+        body.match_indices(prefix).filter_map(move |(start, _)| {
+            let before = &body[..start];
+            let local = before.is_empty()
+                || before.ends_with(|c: char| c.is_whitespace() || "\"'(".contains(c))
+                || (!url.is_empty() && before.ends_with(url));
+            if !local {
+                return None;
+            }
+            let rest = &body[start..];
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "\"'()<>".contains(c))
+                .unwrap_or(rest.len());
+            Some(&rest[..end])
+        })
+    })
+}
+
+fn problem(link: &str, storage: &Storage, published: &HashSet<u32>) -> Option<&'static str> {
+    let path = link.split(['?', '#']).next().unwrap_or(link);
+    if let Some(name) = path.strip_prefix(UPLOADS) {
+        (!storage.exists(name)).then_some("Файл не найден")
+    } else if let Some(id) = path.strip_prefix("/entry/") {
+        let id = id.trim_end_matches('/').parse::<u32>().ok();
+        (!id.is_some_and(|id| published.contains(&id))).then_some("Запись не найдена или скрыта")
+    } else {
+        None
+    }
+}
@@ -3,24 +3,55 @@ use axum::{
     response::{IntoResponse, Response},
 };
 
+use crate::storage::UploadError;
+
 pub enum HistoryError {
     NotFound,
+    Unauthorized,
+    Forbidden,
     InternalError,
+    Invalid(String),
+    Upload(UploadError),
+}
+
+impl HistoryError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            HistoryError::NotFound => StatusCode::NOT_FOUND,
+            HistoryError::Unauthorized => StatusCode::UNAUTHORIZED,
+            HistoryError::Forbidden => StatusCode::FORBIDDEN,
+            HistoryError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            HistoryError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            HistoryError::Upload(UploadError::TooLarge { .. }) => StatusCode::PAYLOAD_TOO_LARGE,
+            HistoryError::Upload(UploadError::Io) => StatusCode::INTERNAL_SERVER_ERROR,
+            HistoryError::Upload(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            HistoryError::NotFound => "Nothing to see here".to_string(),
+            HistoryError::Unauthorized => "Missing, unknown or expired token".to_string(),
+            HistoryError::Forbidden => "The token doesn't allow this".to_string(),
+            HistoryError::InternalError => "Internal Error".to_string(),
+            HistoryError::Invalid(message) => message.clone(),
+            HistoryError::Upload(error) => error.message(),
+        }
+    }
 }
 
 impl IntoResponse for HistoryError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            HistoryError::NotFound => (StatusCode::NOT_FOUND, "Nothing to see here"),
-            HistoryError::InternalError => (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error"),
-        };
-        (status, error_message).into_response()
+        (self.status(), self.message()).into_response()
     }
 }
 
 impl From<sqlx::Error> for HistoryError {
-    fn from(_: sqlx::Error) -> Self {
-        HistoryError::InternalError
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => HistoryError::NotFound,
+            _ => HistoryError::InternalError,
+        }
     }
 }
 
@@ -29,3 +60,9 @@ impl From<std::io::Error> for HistoryError {
         HistoryError::InternalError
     }
 }
+
+impl From<UploadError> for HistoryError {
+    fn from(error: UploadError) -> Self {
+        HistoryError::Upload(error)
+    }
+}
@@ -0,0 +1,253 @@
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, metadata::Orientation, DynamicImage,
+    ImageDecoder, ImageFormat, ImageReader, Limits,
+};
+use sqlx::sqlite::SqlitePool;
+use std::{
+    fs::{remove_file, rename, File},
+    io::{BufWriter, Cursor, Write},
+    path::Path,
+};
+
+use crate::{storage::Storage, HistoryError, Post};
+
+const MAX_DIMENSION: u32 = 10_000;
+const JPEG_QUALITY: u8 = 85;
+const WEBP_QUALITY: f32 = 80.0;
+
+pub struct Variant {
+    pub name: &'static str,
+    pub width: u32,
+    pub height: Option<u32>,
+}
+
+// Thumbnail for the blog list, a full-width image for the entry page and
+// an OpenGraph card cropped to the 1.91:1 ratio social networks expect.
+pub const VARIANTS: [Variant; 3] = [
+    Variant {
+        name: "thumb",
+        width: 480,
+        height: None,
+    },
+    Variant {
+        name: "1200",
+        width: 1200,
+        height: None,
+    },
+    Variant {
+        name: "og",
+        width: 1200,
+        height: Some(630),
+    },
+];
+
+pub fn decode(bytes: &[u8]) -> Result<(DynamicImage, ImageFormat), HistoryError> {
+    let invalid =
+        || HistoryError::Invalid("Обложка должна быть изображением JPEG, PNG или WebP".into());
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|_| invalid())?;
+    let format = match reader.format() {
+        Some(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) => format,
+        _ => return Err(invalid()),
+    };
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder().map_err(|_| invalid())?;
+    // Phones store photos as shot and leave the turn to the EXIF
+    // orientation, which is lost once the pixels are encoded again
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|_| invalid())?;
+    image.apply_orientation(orientation);
+    Ok((image, format))
+}
+
+pub fn extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "png",
+        ImageFormat::WebP => "webp",
+        _ => "jpg",
+    }
+}
+
+// Re-encoding from decoded pixels is what strips EXIF (including GPS)
+// from the stored original: the encoders never copy metadata over.
+pub fn process(storage: &Storage, bytes: &[u8], stem: &str) -> Result<String, HistoryError> {
+    let (image, format) = decode(bytes)?;
+    let file_name = format!("{}.{}", stem, extension(format));
+    if storage.exists(&file_name) && has_variants(storage, &file_name) {
+        return Ok(file_name);
+    }
+    save(&image, format, &path(storage, &file_name)?)?;
+    variants(storage, &image, &file_name)?;
+    Ok(file_name)
+}
+
+pub fn variants(
+    storage: &Storage,
+    image: &DynamicImage,
+    file_name: &str,
+) -> Result<(), HistoryError> {
+    for variant in VARIANTS.iter() {
+        let resized = match variant.height {
+            Some(height) => image.resize_to_fill(variant.width, height, FilterType::Lanczos3),
+            None if image.width() > variant.width => {
+                image.resize(variant.width, MAX_DIMENSION, FilterType::Lanczos3)
+            }
+            None => image.clone(),
+        };
+        save(
+            &resized,
+            ImageFormat::Jpeg,
+            &path(storage, &variant_name(file_name, variant.name, "jpg"))?,
+        )?;
+        save(
+            &resized,
+            ImageFormat::WebP,
+            &path(storage, &variant_name(file_name, variant.name, "webp"))?,
+        )?;
+    }
+    Ok(())
+}
+
+// Covers uploaded before variants existed get them generated from the original.
+pub fn ensure_variants(storage: &Storage, file_name: &str) -> Result<(), HistoryError> {
+    if file_name.is_empty() || has_variants(storage, file_name) {
+        return Ok(());
+    }
+    rebuild(storage, file_name)
+}
+
+pub fn rebuild(storage: &Storage, file_name: &str) -> Result<(), HistoryError> {
+    let bytes = std::fs::read(path(storage, file_name)?)?;
+    let (image, _) = decode(&bytes)?;
+    variants(storage, &image, file_name)
+}
+
+fn has_variants(storage: &Storage, file_name: &str) -> bool {
+    storage.exists(&variant_name(file_name, "og", "jpg"))
+}
+
+pub async fn backfill(db: &SqlitePool, storage: &Storage) -> Result<(), HistoryError> {
+    for post in Post::list(db).await? {
+        let storage = storage.clone();
+        let result = tokio::task::spawn_blocking(move || ensure_variants(&storage, &post.cover))
+            .await
+            .map_err(|_| HistoryError::InternalError)?;
+        if result.is_err() {
+            println!("Can't generate cover variants for post {}", post.id);
+        }
+    }
+    Ok(())
+}
+
+pub fn remove(storage: &Storage, file_name: &str) -> Result<(), HistoryError> {
+    for file in with_variants(file_name) {
+        storage.remove(&file)?;
+    }
+    Ok(())
+}
+
+// The original followed by all of its variants.
+pub fn with_variants(file_name: &str) -> Vec<String> {
+    let mut files = vec![file_name.to_string()];
+    for variant in VARIANTS.iter() {
+        files.push(variant_name(file_name, variant.name, "jpg"));
+        files.push(variant_name(file_name, variant.name, "webp"));
+    }
+    files
+}
+
+pub fn variant_name(file_name: &str, variant: &str, extension: &str) -> String {
+    let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+    format!("{}-{}.{}", stem, variant, extension)
+}
+
+fn path(storage: &Storage, file_name: &str) -> Result<std::path::PathBuf, HistoryError> {
+    storage.path(file_name).ok_or(HistoryError::InternalError)
+}
+
+// Written next to the final name and renamed, so a crash can't leave a
+// truncated image behind
+fn save(image: &DynamicImage, format: ImageFormat, path: &Path) -> Result<(), HistoryError> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".part");
+    match encode(image, format, Path::new(&temporary)) {
+        Ok(()) => Ok(rename(&temporary, path)?),
+        Err(error) => {
+            let _ = remove_file(&temporary);
+            Err(error)
+        }
+    }
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat, path: &Path) -> Result<(), HistoryError> {
+    let mut file = BufWriter::new(File::create(path)?);
+    match format {
+        ImageFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut file, JPEG_QUALITY))
+            .map_err(|_| HistoryError::InternalError)?,
+        // The encoder bundled with `image` is lossless only, which makes
+        // photos larger than the JPEG they are meant to replace
+        ImageFormat::WebP => {
+            let rgba = image.to_rgba8();
+            let encoded =
+                webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height()).encode(WEBP_QUALITY);
+            file.write_all(&encoded)?;
+        }
+        _ => image
+            .write_to(&mut file, format)
+            .map_err(|_| HistoryError::InternalError)?,
+    }
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    // A landscape JPEG marked as shot with the phone turned, which shows
+    // it as portrait
+    fn turned_jpeg() -> Vec<u8> {
+        let mut encoded = Vec::new();
+        RgbImage::from_pixel(64, 48, Rgb([200, 100, 50]))
+            .write_to(&mut Cursor::new(&mut encoded), ImageFormat::Jpeg)
+            .unwrap();
+        let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01".to_vec();
+        exif.extend_from_slice(b"\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0");
+        let mut bytes = encoded[..2].to_vec();
+        bytes.extend_from_slice(&[0xff, 0xe1]);
+        bytes.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        bytes.extend_from_slice(&exif);
+        bytes.extend_from_slice(&encoded[2..]);
+        bytes
+    }
+
+    #[test]
+    fn applies_exif_orientation() {
+        let (image, _) = decode(&turned_jpeg()).ok().unwrap();
+        assert_eq!((image.width(), image.height()), (48, 64));
+    }
+
+    #[test]
+    fn saves_without_leaving_partial_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let storage = Storage::new(dir.path());
+        let file_name = process(&storage, &turned_jpeg(), "cover").ok().unwrap();
+        let mut files: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        let mut expected = with_variants(&file_name);
+        expected.sort();
+        assert_eq!(files, expected);
+    }
+}
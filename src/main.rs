@@ -1,88 +1,182 @@
 use axum::{
     extract::DefaultBodyLimit,
+    middleware,
     routing::{get, post},
     Router,
 };
 use axum_login::{
     axum_sessions::{async_session::CookieStore as SessionStore, SessionLayer},
     extractors::AuthContext,
-    memory_store::MemoryStore as AuthMemoryStore,
-    AuthLayer, AuthUser, RequireAuthorizationLayer,
+    AuthLayer, RequireAuthorizationLayer, SqliteStore,
 };
+use clap::Parser;
+use hyper::server::accept;
 use rand::Rng;
 use serde::Deserialize;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
-use std::{collections::HashMap, env, net::SocketAddr, process, sync::Arc};
-use tokio::sync::RwLock;
+use sqlx::sqlite::SqlitePool;
+use std::{
+    env,
+    fs::{create_dir_all, remove_file},
+    net::SocketAddr,
+    process,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::UnixListener;
 use tower_http::{limit::RequestBodyLimitLayer, services::ServeDir};
 
-use auth::{Role, User};
+use analytics::Analytics;
+use auth::Role;
+use cli::{Cli, Command};
+use config::{Config, Site};
 use error::HistoryError;
-use models::{book::Book, post::Post, publication::Publication, text::Text};
+use models::{book::Book, post::Post, publication::Publication, text::Text, user::User};
+use storage::Storage;
 use views::*;
 
+pub mod analytics;
 pub mod auth;
+pub mod backup;
+pub mod cli;
+pub mod config;
+pub mod consistency;
 pub mod error;
+pub mod images;
+pub mod markup;
 pub mod models;
+pub mod slug;
+pub mod storage;
+pub mod transfer;
 pub mod views;
 
-const DB_FILE: &str = "db/history.db";
-const IMG_PATH: &str = "static/uploads";
-
-type Auth = AuthContext<usize, User, AuthMemoryStore<usize, User>, Role>;
-type RequireAuth = RequireAuthorizationLayer<usize, User, Role>;
+type Auth = AuthContext<i64, User, SqliteStore<User, Role>, Role>;
+type RequireAuth = RequireAuthorizationLayer<i64, User, Role>;
 
 #[derive(Deserialize, Debug)]
 pub struct LoginInput {
-    secret: String,
+    name: String,
+    password: String,
 }
 
 pub struct HistoryState {
-    secret: String,
     db: SqlitePool,
+    storage: Storage,
+    site: Site,
+    backup: config::Backup,
+    analytics: Analytics,
 }
 
 #[tokio::main]
 async fn main() {
-    let secret = env::var("SECRET").unwrap_or_else(|_| {
-        println!("SECRET must be set");
-        process::exit(0);
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+    let serving = matches!(command, Command::Serve);
+    let config = Config::load(cli.overrides, serving).unwrap_or_else(|problems| {
+        println!("Invalid configuration:");
+        for problem in problems {
+            println!("  {}", problem);
+        }
+        process::exit(1);
     });
-    let db = SqlitePoolOptions::new()
-        .connect(DB_FILE)
+    let db = models::connect(&config.database)
         .await
-        .unwrap_or_else(|_| {
-            println!("Can't find db file");
-            process::exit(0);
+        .unwrap_or_else(|error| {
+            println!(
+                "Can't open db file {}: {}",
+                config.database.display(),
+                error
+            );
+            process::exit(1);
         });
+    if let Err(error) = create_dir_all(&config.uploads) {
+        println!("Can't create {}: {}", config.uploads.display(), error);
+        process::exit(1);
+    }
+    let storage = Storage::new(&config.uploads);
+
+    match command {
+        Command::Serve => serve(config, db, storage).await,
+        command => cli::run(command, &config, &db, &storage).await,
+    }
+}
+
+async fn serve(config: Config, db: SqlitePool, storage: Storage) {
+    ensure_admin(&db).await;
+    let (backfill_db, backfill_storage) = (db.clone(), storage.clone());
+    tokio::spawn(async move {
+        if images::backfill(&backfill_db, &backfill_storage)
+            .await
+            .is_err()
+        {
+            println!("Can't check cover variants");
+        }
+    });
+    backup::schedule(db.clone(), storage.clone(), config.backup.clone());
     let state = Arc::new(HistoryState {
-        secret: secret.clone(),
-        db,
+        db: db.clone(),
+        storage,
+        site: config.site.clone(),
+        backup: config.backup.clone(),
+        analytics: Analytics::new(config.trusted_proxies.clone()),
     });
 
     let session_secret = rand::thread_rng().gen::<[u8; 64]>();
     let session_store = SessionStore::new();
-    let session_layer = SessionLayer::new(session_store, &session_secret);
+    let session_layer = SessionLayer::new(session_store, &session_secret)
+        .with_session_ttl(Some(Duration::from_secs(config.session_hours * 60 * 60)));
 
-    let store = Arc::new(RwLock::new(HashMap::default()));
-    let user = User::new(secret);
-    store.write().await.insert(user.get_id(), user);
-    let user_store = AuthMemoryStore::new(&store);
+    let user_store =
+        SqliteStore::<User, Role>::new(db).with_query("SELECT * FROM users WHERE id = ?");
     let auth_layer = AuthLayer::new(user_store, &session_secret);
 
     let history = Router::new()
+        .route("/admin", get(dashboard::page))
+        .route("/stats", get(stats::page))
         // Posts
         .route("/posts", get(posts::all))
+        .route("/posts/bulk", post(posts::bulk))
         .route("/posts/create", get(posts::add).post(posts::create))
         .route("/posts/update/:id", get(posts::edit).post(posts::update))
         .route("/posts/delete/:id", post(posts::delete))
+        .route("/posts/preview", post(posts::preview))
+        // Media
+        .route("/media", get(media::all))
+        .route("/media/create", post(media::create))
+        .route("/media/upload", post(media::upload))
+        .route("/media/update/:id", get(media::edit).post(media::update))
+        .route("/media/delete/:id", post(media::delete))
+        // Uploads
+        .route("/uploads", get(uploads::check))
+        .route("/uploads/cleanup", post(uploads::cleanup))
+        // Backups
+        .route("/backups", get(backups::all))
+        .route("/backups/create", post(backups::create))
+        .route("/backups/:name/restore", post(backups::restore))
+        .route("/backups/:name/:file", get(backups::download))
+        // Transfer
+        .route("/transfer", get(transfers::page))
+        .route("/transfer/export", get(transfers::export))
+        .route("/transfer/import", post(transfers::import))
+        // Terms
+        .route("/terms", get(terms::all))
+        .route("/terms/create", post(terms::create))
+        .route("/terms/update/:id", post(terms::update))
+        .route("/terms/delete/:id", post(terms::delete))
+        // Tokens
+        .route("/tokens", get(tokens::all))
+        .route("/tokens/create", post(tokens::create))
+        .route("/tokens/delete/:id", post(tokens::delete))
         // Books
         .route("/books", get(books::all))
+        .route("/books/bulk", post(books::bulk))
+        .route("/books/reorder", post(books::reorder))
         .route("/books/create", get(books::add).post(books::create))
         .route("/books/update/:id", get(books::edit).post(books::update))
         .route("/books/delete/:id", post(books::delete))
         // Publications
         .route("/publications", get(publications::all))
+        .route("/publications/bulk", post(publications::bulk))
+        .route("/publications/reorder", post(publications::reorder))
         .route(
             "/publications/create",
             get(publications::add).post(publications::create),
@@ -94,35 +188,101 @@ async fn main() {
         .route("/publications/delete/:id", post(publications::delete))
         // Texts
         .route("/texts", get(texts::all))
+        .route("/texts/bulk", post(texts::bulk))
+        .route("/texts/reorder", post(texts::reorder))
         .route("/texts/create", get(texts::add).post(texts::create))
         .route("/texts/update/:id", get(texts::edit).post(texts::update))
         .route("/texts/delete/:id", post(texts::delete))
         // Routes above are protected
         .route_layer(RequireAuth::login_with_role(Role::Admin..))
         // Static
-        .nest_service("/static", ServeDir::new("static"))
+        .nest_service("/static/uploads", ServeDir::new(&config.uploads))
+        .nest_service("/static", ServeDir::new(&config.static_dir))
         // Admin
         .route("/login", get(admin::form).post(admin::login))
         .route("/logout", get(admin::logout))
         // Handlers
         .route("/", get(handlers::home))
         .route("/blog", get(handlers::blog))
+        .route("/blog/rss.xml", get(handlers::rss))
+        .route("/blog/:year", get(handlers::blog_year))
+        .route("/blog/:year/:month", get(handlers::blog_month))
+        .route("/blog/tag/:slug", get(handlers::blog_tag))
+        .route("/blog/tag/:slug/rss.xml", get(handlers::tag_rss))
         .route("/entry/:id", get(handlers::entry))
+        .route("/entry/:id/sources.bib", get(handlers::sources_bibtex))
+        .route("/download/:kind/:id", get(handlers::download))
+        // API
+        .nest("/api/v1", api::router(&config.api))
+        .route("/api/openapi.json", get(api::docs::spec))
+        .route("/api/docs", get(api::docs::redirect))
+        .route("/api/docs/", get(api::docs::index))
+        .route("/api/docs/*path", get(api::docs::viewer))
         // System
         .fallback(nothing)
         // Layers
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            analytics::track,
+        ))
         .layer(auth_layer)
         .layer(session_layer)
         .layer(DefaultBodyLimit::disable())
-        .layer(RequestBodyLimitLayer::new(8 * 1024 * 1024 /* 8mb */))
+        .layer(RequestBodyLimitLayer::new(config.body_limit()))
         .with_state(state);
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8888));
-    println!("Listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(history.into_make_service())
+    match &config.socket {
+        Some(path) => {
+            // A socket left over from a previous run would make bind fail
+            let _ = remove_file(path);
+            let listener = UnixListener::bind(path).unwrap_or_else(|error| {
+                println!("Can't listen on {}: {}", path.display(), error);
+                process::exit(1);
+            });
+            println!("Listening on {}", path.display());
+            let incoming = accept::poll_fn(move |cx| {
+                listener
+                    .poll_accept(cx)
+                    .map(|result| Some(result.map(|(stream, _)| stream)))
+            });
+            axum::Server::builder(incoming)
+                .serve(history.into_make_service())
+                .await
+                .unwrap();
+        }
+        None => {
+            let addr: SocketAddr = config.bind.parse().unwrap();
+            let server = axum::Server::try_bind(&addr).unwrap_or_else(|error| {
+                println!("Can't listen on {}: {}", addr, error);
+                process::exit(1);
+            });
+            println!("Listening on {}", addr);
+            server
+                .serve(history.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        }
+    }
+}
+
+// Before the first account is created with `history user add`, SECRET
+// keeps working as the password of an "admin" account.
+async fn ensure_admin(db: &SqlitePool) {
+    if User::count(db).await.unwrap_or(0) > 0 {
+        return;
+    }
+    let Ok(secret) = env::var("SECRET") else {
+        println!("No users yet: create one with `history user add <name>` or set SECRET");
+        process::exit(1);
+    };
+    if User::create(db, "admin", &auth::hash_password(&secret))
         .await
-        .unwrap();
+        .is_err()
+    {
+        println!("Can't create the admin user");
+        process::exit(1);
+    }
+    println!("Created user admin with the password from SECRET");
 }
 
 pub async fn nothing() -> HistoryError {
@@ -0,0 +1,177 @@
+use pulldown_cmark::{html::push_html, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use footnotes::{Footnotes, Note};
+use typography::Typograph;
+
+pub mod footnotes;
+pub mod typography;
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, ToSchema)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Html,
+    Markdown,
+}
+
+impl Format {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Markdown => "markdown",
+        }
+    }
+}
+
+impl From<&str> for Format {
+    fn from(value: &str) -> Self {
+        match value {
+            "markdown" => Format::Markdown,
+            _ => Format::Html,
+        }
+    }
+}
+
+pub struct Rendered {
+    pub body: String,
+    pub notes: Vec<Note>,
+}
+
+pub fn render(format: Format, source: &str) -> Rendered {
+    let mut footnotes = Footnotes::default();
+    let body = match format {
+        // Summernote output is written by editors in the admin and trusted as is
+        Format::Html => footnotes::collect_html(source, &mut footnotes),
+        Format::Markdown => markdown(source, &mut footnotes),
+    };
+    Rendered {
+        body,
+        notes: footnotes.notes(),
+    }
+}
+
+pub fn check(format: Format, source: &str) -> Vec<String> {
+    let mut footnotes = Footnotes::default();
+    match format {
+        Format::Html => {
+            footnotes::collect_html(source, &mut footnotes);
+        }
+        Format::Markdown => {
+            markdown(source, &mut footnotes);
+        }
+    }
+    footnotes.problems()
+}
+
+fn markdown(source: &str, footnotes: &mut Footnotes) -> String {
+    let options =
+        Options::ENABLE_FOOTNOTES | Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut typograph = Typograph::default();
+    let mut in_code = false;
+    let mut body = Vec::new();
+    let mut definition: Option<(String, Vec<Event>)> = None;
+    let mut run = String::new();
+    for event in Parser::new_ext(source, options) {
+        if !matches!(event, Event::Text(_)) {
+            footnotes.unresolved(&run);
+            run.clear();
+        }
+        let event = match event {
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                definition = Some((label.to_string(), Vec::new()));
+                continue;
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                if let Some((label, events)) = definition.take() {
+                    let mut html = String::new();
+                    push_html(&mut html, events.into_iter());
+                    footnotes.define(&label, sanitize(&html));
+                }
+                continue;
+            }
+            Event::FootnoteReference(label) => {
+                Event::InlineHtml(footnotes.reference(&label).into())
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code = true;
+                event
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code = false;
+                event
+            }
+            Event::Text(text) if !in_code => {
+                // The parser may split `[^x]` over several text events
+                run.push_str(&text);
+                Event::Text(typograph.apply(&text).into())
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                typograph.break_line();
+                event
+            }
+            Event::Start(
+                Tag::Paragraph
+                | Tag::Heading { .. }
+                | Tag::Item
+                | Tag::BlockQuote(_)
+                | Tag::TableCell,
+            ) => {
+                typograph.start_block();
+                event
+            }
+            _ => event,
+        };
+        match definition.as_mut() {
+            Some((_, events)) => events.push(event),
+            None => body.push(event),
+        }
+    }
+    footnotes.unresolved(&run);
+    let mut html = String::new();
+    push_html(&mut html, body.into_iter());
+    sanitize(&html)
+}
+
+pub fn sanitize(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_tag_attributes("sup", ["class", "id"])
+        .clean(html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(source: &str) -> String {
+        render(Format::Markdown, source).body
+    }
+
+    #[test]
+    fn paragraph_opens_a_quote_after_one_left_open() {
+        assert_eq!(
+            html("Он сказал \"нет.\n\n\"Да\", ответила она."),
+            "<p>Он сказал «нет.</p>\n<p>«Да», ответила она.</p>\n"
+        );
+    }
+
+    #[test]
+    fn paragraph_starts_with_a_dash() {
+        assert_eq!(
+            html("Кто там?\n\n\\- Почтальон."),
+            "<p>Кто там?</p>\n<p>— Почтальон.</p>\n"
+        );
+    }
+
+    #[test]
+    fn cell_starts_with_a_dash() {
+        assert_eq!(
+            html("| а | б |\n|---|---|\n| да | - |"),
+            "<table><thead><tr><th>а</th><th>б</th></tr></thead><tbody>\n\
+             <tr><td>да</td><td>—</td></tr>\n</tbody></table>\n"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct Note {
+    pub number: usize,
+    pub html: String,
+    #[serde(skip)]
+    pub backrefs: Vec<String>,
+}
+
+// Collects `[^label]` references and their definitions, numbering notes in
+// the order they are first referenced in the text.
+#[derive(Default)]
+pub struct Footnotes {
+    labels: Vec<String>,
+    occurrences: Vec<usize>,
+    definitions: HashMap<String, String>,
+    unresolved: Vec<String>,
+}
+
+impl Footnotes {
+    pub fn reference(&mut self, label: &str) -> String {
+        let index = match self.labels.iter().position(|l| l == label) {
+            Some(index) => index,
+            None => {
+                self.labels.push(label.to_string());
+                self.occurrences.push(0);
+                self.labels.len() - 1
+            }
+        };
+        self.occurrences[index] += 1;
+        let number = index + 1;
+        format!(
+            r##"<sup class="footnote-ref" id="{}"><a href="#fn-{}">{}</a></sup>"##,
+            backref_id(number, self.occurrences[index]),
+            number,
+            number
+        )
+    }
+
+    // Markdown leaves references without a definition as plain text, they are
+    // only remembered here to be reported on save.
+    pub fn unresolved(&mut self, text: &str) {
+        let mut rest = text;
+        while let Some(start) = rest.find("[^") {
+            rest = &rest[start + 2..];
+            if let Some((label, after)) = label(rest) {
+                if !after.starts_with(':') {
+                    self.unresolved.push(label.to_string());
+                }
+                rest = after;
+            }
+        }
+    }
+
+    pub fn define(&mut self, label: &str, html: String) {
+        self.definitions.insert(label.to_string(), html);
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let dangling: Vec<String> = self
+            .labels
+            .iter()
+            .chain(self.unresolved.iter())
+            .filter(|label| !self.definitions.contains_key(*label))
+            .map(|label| format!("[^{}]", label))
+            .collect();
+        if !dangling.is_empty() {
+            problems.push(format!(
+                "Сноски без текста ({}): {}",
+                dangling.len(),
+                dangling.join(", ")
+            ));
+        }
+        let mut unused: Vec<String> = self
+            .definitions
+            .keys()
+            .filter(|label| !self.labels.contains(label))
+            .map(|label| format!("[^{}]", label))
+            .collect();
+        if !unused.is_empty() {
+            unused.sort();
+            problems.push(format!(
+                "Тексты сносок без ссылок ({}): {}",
+                unused.len(),
+                unused.join(", ")
+            ));
+        }
+        problems
+    }
+
+    pub fn notes(mut self) -> Vec<Note> {
+        self.labels
+            .iter()
+            .zip(self.occurrences.iter())
+            .enumerate()
+            .map(|(index, (label, occurrences))| {
+                let number = index + 1;
+                Note {
+                    number,
+                    html: self.definitions.remove(label).unwrap_or_default(),
+                    backrefs: (1..=*occurrences)
+                        .map(|occurrence| backref_id(number, occurrence))
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+fn backref_id(number: usize, occurrence: usize) -> String {
+    if occurrence == 1 {
+        format!("fnref-{}", number)
+    } else {
+        format!("fnref-{}-{}", number, occurrence)
+    }
+}
+
+// Summernote has no notion of footnotes, so in HTML posts they are typed as
+// plain text: `[^1]` in the body and a `[^1]: ...` paragraph for the note.
+pub fn collect_html(source: &str, footnotes: &mut Footnotes) -> String {
+    let mut body = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("[^") {
+        let Some((label, after)) = label(&rest[start + 2..]) else {
+            body.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        };
+        if let Some(definition) = after.strip_prefix(':') {
+            let before = rest[..start].trim_end();
+            if let Some(prefix) = before.strip_suffix("<p>") {
+                let end = definition.find("</p>").unwrap_or(definition.len());
+                footnotes.define(label, definition[..end].trim().to_string());
+                body.push_str(prefix);
+                rest = definition[end..].strip_prefix("</p>").unwrap_or("");
+            } else {
+                let end = definition.find('\n').unwrap_or(definition.len());
+                footnotes.define(label, definition[..end].trim().to_string());
+                body.push_str(&rest[..start]);
+                rest = &definition[end..];
+            }
+        } else {
+            body.push_str(&rest[..start]);
+            body.push_str(&footnotes.reference(label));
+            rest = after;
+        }
+    }
+    body.push_str(rest);
+    body
+}
+
+fn label(text: &str) -> Option<(&str, &str)> {
+    let end = text.find(']')?;
+    let label = &text[..end];
+    if label.is_empty()
+        || label.len() > 32
+        || label.contains(|c: char| c.is_whitespace() || c == '<')
+    {
+        return None;
+    }
+    Some((label, &text[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(source: &str) -> (String, Footnotes) {
+        let mut footnotes = Footnotes::default();
+        let body = collect_html(source, &mut footnotes);
+        (body, footnotes)
+    }
+
+    #[test]
+    fn notes_are_numbered_by_first_reference() {
+        let (body, footnotes) =
+            collect("<p>Один[^b], два[^a].</p>\n<p>[^a]: Первая</p>\n<p>[^b]: Вторая</p>");
+        assert_eq!(
+            body,
+            "<p>Один<sup class=\"footnote-ref\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup>, \
+             два<sup class=\"footnote-ref\" id=\"fnref-2\"><a href=\"#fn-2\">2</a></sup>.</p>\n\n"
+        );
+        let notes = footnotes.notes();
+        let notes: Vec<(usize, &str)> = notes
+            .iter()
+            .map(|note| (note.number, note.html.as_str()))
+            .collect();
+        assert_eq!(notes, vec![(1, "Вторая"), (2, "Первая")]);
+    }
+
+    #[test]
+    fn repeated_reference_keeps_its_number() {
+        let mut footnotes = Footnotes::default();
+        footnotes.reference("a");
+        footnotes.reference("b");
+        assert_eq!(
+            footnotes.reference("a"),
+            "<sup class=\"footnote-ref\" id=\"fnref-1-2\"><a href=\"#fn-1\">1</a></sup>"
+        );
+        footnotes.define("a", "Текст".to_string());
+        footnotes.define("b", "Другой".to_string());
+        assert_eq!(footnotes.notes().len(), 2);
+    }
+
+    #[test]
+    fn every_reference_gets_a_back_link() {
+        let mut footnotes = Footnotes::default();
+        footnotes.reference("a");
+        footnotes.reference("a");
+        footnotes.reference("a");
+        footnotes.define("a", "Текст".to_string());
+        assert_eq!(
+            footnotes.notes()[0].backrefs,
+            vec!["fnref-1", "fnref-1-2", "fnref-1-3"]
+        );
+    }
+
+    #[test]
+    fn undefined_references_are_reported() {
+        let (_, mut footnotes) = collect("<p>Текст[^a].</p>");
+        footnotes.unresolved("Ещё [^b] и [^c]: определение");
+        assert_eq!(
+            footnotes.problems(),
+            vec!["Сноски без текста (2): [^a], [^b]"]
+        );
+        assert_eq!(footnotes.notes()[0].html, "");
+    }
+
+    #[test]
+    fn unused_definitions_are_reported() {
+        let (body, footnotes) =
+            collect("<p>Текст[^a].</p>\n<p>[^a]: Есть</p>\n<p>[^z]: Лишняя</p>\n<p>[^m]: Тоже</p>");
+        assert!(!body.contains("Лишняя"));
+        assert_eq!(
+            footnotes.problems(),
+            vec!["Тексты сносок без ссылок (2): [^m], [^z]"]
+        );
+        assert_eq!(footnotes.notes().len(), 1);
+    }
+}
@@ -0,0 +1,88 @@
+const NBSP: char = '\u{a0}';
+
+// Russian typographic conventions: «ёлочки» for outer quotes, „лапки“ for
+// nested ones, em-dashes glued to the preceding word with a non-breaking space.
+pub struct Typograph {
+    prev: char,
+    quotes: usize,
+}
+
+impl Default for Typograph {
+    fn default() -> Self {
+        Self {
+            prev: ' ',
+            quotes: 0,
+        }
+    }
+}
+
+impl Typograph {
+    pub fn break_line(&mut self) {
+        self.prev = ' ';
+    }
+
+    // A quote left open doesn't carry over into the next paragraph or cell
+    pub fn start_block(&mut self) {
+        self.prev = ' ';
+        self.quotes = 0;
+    }
+
+    pub fn apply(&mut self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                '"' => {
+                    let opening = self.prev.is_whitespace() || "([{«„—-".contains(self.prev);
+                    out.push(self.quote(opening));
+                }
+                '-' if chars.get(i + 1) == Some(&'-') => {
+                    while chars.get(i + 1) == Some(&'-') {
+                        i += 1;
+                    }
+                    dash(&mut out);
+                }
+                '-' if self.prev == ' ' && chars.get(i + 1).is_none_or(|n| n.is_whitespace()) => {
+                    dash(&mut out);
+                }
+                '.' if chars.get(i + 1) == Some(&'.') && chars.get(i + 2) == Some(&'.') => {
+                    i += 2;
+                    out.push('…');
+                }
+                _ => out.push(c),
+            }
+            self.prev = out.chars().next_back().unwrap_or(c);
+            i += 1;
+        }
+        out
+    }
+
+    fn quote(&mut self, opening: bool) -> char {
+        if opening {
+            self.quotes += 1;
+            if self.quotes > 1 {
+                '„'
+            } else {
+                '«'
+            }
+        } else {
+            let nested = self.quotes > 1;
+            self.quotes = self.quotes.saturating_sub(1);
+            if nested {
+                '“'
+            } else {
+                '»'
+            }
+        }
+    }
+}
+
+fn dash(out: &mut String) {
+    if out.ends_with(' ') {
+        out.pop();
+        out.push(NBSP);
+    }
+    out.push('—');
+}
@@ -1,4 +1,239 @@
 pub mod book;
+pub mod media;
+pub mod overview;
 pub mod post;
 pub mod publication;
+pub mod section;
+pub mod source;
+pub mod tag;
+pub mod term;
 pub mod text;
+pub mod token;
+pub mod user;
+pub mod visit;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{Sqlite, SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
+    Error, Transaction,
+};
+use std::{fs::create_dir_all, path::Path};
+use utoipa::ToSchema;
+
+// Migrations from `migrations/` are compiled into the binary; sqlx records
+// the applied ones in the `_sqlx_migrations` table.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// Opens the database, creating the file for a new environment, and brings
+// its schema up to date.
+pub async fn connect(path: &Path) -> Result<SqlitePool, Error> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let db = SqlitePoolOptions::new().connect_with(options).await?;
+    MIGRATOR.run(&db).await?;
+    Ok(db)
+}
+
+// For tests; every connection to :memory: is a separate database
+#[cfg(test)]
+pub(crate) async fn memory() -> SqlitePool {
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    MIGRATOR.run(&db).await.unwrap();
+    db
+}
+
+pub async fn applied_migrations(db: &SqlitePool) -> Result<Vec<(i64, String)>, Error> {
+    sqlx::query_as("SELECT version, description FROM _sqlx_migrations ORDER BY version")
+        .fetch_all(db)
+        .await
+}
+
+pub(crate) async fn ordered_ids(db: &SqlitePool, table: &str) -> Result<Vec<u32>, Error> {
+    sqlx::query_scalar(&format!("SELECT id FROM {} ORDER BY position, id", table))
+        .fetch_all(db)
+        .await
+}
+
+// The first entry of a catalog table with this link, which is how an import
+// recognises entries it already has.
+pub(crate) async fn id_by_link(
+    tx: &mut Transaction<'_, Sqlite>,
+    table: &str,
+    link: &str,
+) -> Result<Option<u32>, Error> {
+    sqlx::query_scalar(&format!(
+        "SELECT id FROM {} WHERE link = ? ORDER BY id LIMIT 1",
+        table
+    ))
+    .bind(link)
+    .fetch_optional(&mut *tx)
+    .await
+}
+
+// Numbers the rows of a catalog table in the given order. Nothing changes
+// unless `ids` lists every row exactly once, so an ordering made from a
+// stale page can't leave two entries on the same position.
+pub(crate) async fn reorder(db: &SqlitePool, table: &str, ids: &[u32]) -> Result<bool, Error> {
+    let mut tx = db.begin().await?;
+    let mut existing: Vec<u32> = sqlx::query_scalar(&format!("SELECT id FROM {}", table))
+        .fetch_all(&mut *tx)
+        .await?;
+    let mut given = ids.to_vec();
+    existing.sort_unstable();
+    given.sort_unstable();
+    if existing != given {
+        return Ok(false);
+    }
+    for (position, id) in ids.iter().enumerate() {
+        sqlx::query(&format!("UPDATE {} SET position = ? WHERE id = ?", table))
+            .bind(position as i64 + 1)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+const PER_PAGE: u32 = 20;
+const MAX_PER_PAGE: u32 = 100;
+// Far past the end of any list here, and keeps the offset small
+const MAX_PAGE: u32 = 100_000;
+
+// Page numbers start at 1; out of range values are clamped rather than
+// rejected, so a hand-edited query string still gets a page.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct Pagination {
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            per_page: PER_PAGE,
+        }
+    }
+}
+
+impl Pagination {
+    pub fn limit(&self) -> u32 {
+        self.per_page.clamp(1, MAX_PER_PAGE)
+    }
+
+    pub fn page(&self) -> u32 {
+        self.page.clamp(1, MAX_PAGE)
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() as i64 - 1) * self.limit() as i64
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: i64,
+    pub pages: u32,
+    // Other query parameters, such as a search, kept in page links
+    #[serde(skip)]
+    pub filter: String,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, pagination: Pagination, total: i64) -> Self {
+        let per_page = pagination.limit();
+        Self {
+            items,
+            page: pagination.page(),
+            per_page,
+            total,
+            pages: ((total + per_page as i64 - 1) / per_page as i64) as u32,
+            filter: String::new(),
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            page: self.page,
+            per_page: self.per_page,
+            total: self.total,
+            pages: self.pages,
+            filter: self.filter,
+        }
+    }
+
+    pub fn with_filter(mut self, name: &str, value: &str) -> Self {
+        if !value.is_empty() {
+            let pair = serde_urlencoded::to_string([(name, value)]).unwrap_or_default();
+            self.filter.push('&');
+            self.filter.push_str(&pair);
+        }
+        self
+    }
+
+    pub fn prev(&self) -> Option<u32> {
+        (self.page > 1).then(|| (self.page - 1).min(self.pages.max(1)))
+    }
+
+    pub fn next(&self) -> Option<u32> {
+        (self.page < self.pages).then_some(self.page + 1)
+    }
+
+    // Relative to the current path, so the same links work on every list
+    pub fn link(&self, page: u32) -> String {
+        let mut link = format!("?page={}", page);
+        if self.per_page != PER_PAGE {
+            link.push_str(&format!("&per_page={}", self.per_page));
+        }
+        link.push_str(&self.filter);
+        link
+    }
+
+    // The first, the last and a few pages around the current one, with
+    // `None` where numbers are skipped.
+    pub fn numbers(&self) -> Vec<Option<u32>> {
+        let mut numbers = Vec::new();
+        for number in 1..=self.pages {
+            let near = number.abs_diff(self.page) <= 2;
+            if number == 1 || number == self.pages || near {
+                numbers.push(Some(number));
+            } else if numbers.last() != Some(&None) {
+                numbers.push(None);
+            }
+        }
+        numbers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn far_pages_do_not_overflow_the_offset() {
+        let pagination = Pagination {
+            page: u32::MAX,
+            per_page: MAX_PER_PAGE,
+        };
+        assert_eq!(pagination.page(), MAX_PAGE);
+        assert_eq!(pagination.offset(), (MAX_PAGE as i64 - 1) * 100);
+        let page = Page::<u32>::new(Vec::new(), pagination, 3);
+        assert_eq!(page.prev(), Some(1));
+        assert_eq!(page.next(), None);
+    }
+}
@@ -1,32 +1,78 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{
-    query, query_as,
-    sqlite::{SqlitePool, SqliteQueryResult},
-    Error, FromRow,
+    query, query_as, query_scalar,
+    sqlite::{Sqlite, SqlitePool, SqliteQueryResult},
+    Error, FromRow, Transaction,
 };
+use utoipa::ToSchema;
 
-#[derive(Deserialize, Debug)]
+use crate::models::{
+    self,
+    term::{Entry, Facets},
+    Page, Pagination,
+};
+
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct NewBook {
     pub name: String,
     pub link: String,
     pub description: String,
 }
 
-#[derive(Deserialize, FromRow, Clone)]
+#[derive(Serialize, Deserialize, FromRow, Clone, ToSchema)]
 pub struct Book {
     pub id: u32,
     pub name: String,
     pub link: String,
     pub description: String,
+    // Where the entry goes on the home page; exports made before it
+    // existed keep the order of ids
+    #[serde(default)]
+    pub position: i64,
+    // Drafts are only seen in the admin
+    #[serde(default)]
+    pub draft: bool,
+    // Followed links from the site, see `Section::download`
+    #[serde(default)]
+    pub downloads: i64,
 }
 
 impl Book {
     pub async fn list(db: &SqlitePool) -> Result<Vec<Self>, Error> {
-        query_as::<_, Book>("SELECT * FROM books ORDER BY id")
+        query_as::<_, Book>("SELECT * FROM books ORDER BY position, id")
             .fetch_all(db)
             .await
     }
 
+    pub async fn faceted(db: &SqlitePool, facets: &Facets) -> Result<Vec<Self>, Error> {
+        let sql = format!(
+            "SELECT * FROM books WHERE draft = 0 AND {} ORDER BY position, id",
+            Facets::condition(Entry::Book, "books")
+        );
+        let mut items = query_as::<_, Book>(&sql);
+        for value in facets.binds() {
+            items = items.bind(value);
+        }
+        items.fetch_all(db).await
+    }
+
+    pub async fn published(db: &SqlitePool) -> Result<Vec<Self>, Error> {
+        query_as::<_, Book>("SELECT * FROM books WHERE draft = 0 ORDER BY position, id")
+            .fetch_all(db)
+            .await
+    }
+
+    pub async fn page(db: &SqlitePool, pagination: Pagination) -> Result<Page<Self>, Error> {
+        let items =
+            query_as::<_, Book>("SELECT * FROM books ORDER BY position, id LIMIT ? OFFSET ?")
+                .bind(pagination.limit())
+                .bind(pagination.offset())
+                .fetch_all(db)
+                .await?;
+        let (total,): (i64,) = query_as("SELECT count(*) FROM books").fetch_one(db).await?;
+        Ok(Page::new(items, pagination, total))
+    }
+
     pub async fn fetch(db: &SqlitePool, id: u32) -> Result<Self, Error> {
         query_as::<_, Book>("SELECT * FROM books WHERE id = ?")
             .bind(id)
@@ -35,12 +81,15 @@ impl Book {
     }
 
     pub async fn create(db: &SqlitePool, new_book: NewBook) -> Result<SqliteQueryResult, Error> {
-        query("INSERT into books (name, link, description) values (?, ?, ?)")
-            .bind(new_book.name)
-            .bind(new_book.link)
-            .bind(new_book.description)
-            .execute(db)
-            .await
+        query(
+            "INSERT into books (name, link, description, position) \
+             values (?, ?, ?, (SELECT coalesce(max(position), 0) + 1 FROM books))",
+        )
+        .bind(new_book.name)
+        .bind(new_book.link)
+        .bind(new_book.description)
+        .execute(db)
+        .await
     }
 
     pub async fn update(
@@ -57,6 +106,39 @@ impl Book {
             .await
     }
 
+    // Matched by link, since ids differ between sites; returns the id the
+    // entry has here, which is a new one unless the link is already listed.
+    pub async fn upsert(tx: &mut Transaction<'_, Sqlite>, book: &Book) -> Result<u32, Error> {
+        let id = models::id_by_link(tx, "books", &book.link).await?;
+        query_scalar(
+            "INSERT into books (id, name, link, description, position, draft, downloads) \
+             values (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, link = excluded.link, \
+             description = excluded.description, position = excluded.position, \
+             draft = excluded.draft, downloads = excluded.downloads RETURNING id",
+        )
+        .bind(id)
+        .bind(&book.name)
+        .bind(&book.link)
+        .bind(&book.description)
+        .bind(match book.position {
+            0 => book.id as i64,
+            position => position,
+        })
+        .bind(book.draft)
+        .bind(book.downloads)
+        .fetch_one(&mut *tx)
+        .await
+    }
+
+    pub async fn ids(db: &SqlitePool) -> Result<Vec<u32>, Error> {
+        models::ordered_ids(db, "books").await
+    }
+
+    pub async fn reorder(db: &SqlitePool, ids: &[u32]) -> Result<bool, Error> {
+        models::reorder(db, "books", ids).await
+    }
+
     pub async fn delete(db: &SqlitePool, id: u32) -> Result<SqliteQueryResult, Error> {
         query("DELETE FROM books WHERE id = ?")
             .bind(id)
@@ -0,0 +1,268 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    query, query_as,
+    sqlite::{Sqlite, SqlitePool, SqliteQueryResult},
+    Error, FromRow, Transaction,
+};
+
+use crate::{
+    images,
+    models::{Page, Pagination},
+};
+
+// How many posts and catalog entries reference the file, either as a cover
+// or inside their text; `{file}` is the SQL expression of its name.
+const USES: &str = "(SELECT count(*) FROM posts WHERE posts.cover = {file} \
+        OR instr(posts.body, {file}) > 0) \
+    + (SELECT count(*) FROM books WHERE instr(books.description, {file}) > 0) \
+    + (SELECT count(*) FROM publications WHERE instr(publications.description, {file}) > 0) \
+    + (SELECT count(*) FROM texts WHERE instr(texts.description, {file}) > 0)";
+
+fn select() -> String {
+    format!(
+        "SELECT media.*, {} AS uses FROM media",
+        USES.replace("{file}", "media.file_name")
+    )
+}
+
+// `?1` is the pattern from `search_pattern`
+const SEARCH: &str = "original_name LIKE ?1 ESCAPE '\\' OR alt LIKE ?1 ESCAPE '\\' \
+    OR caption LIKE ?1 ESCAPE '\\'";
+
+// Finds the text anywhere, with `%` and `_` in it taken literally
+fn search_pattern(search: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in search.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+#[derive(Debug)]
+pub struct NewMedia {
+    pub file_name: String,
+    pub original_name: String,
+    pub mime: String,
+    pub size: i64,
+    pub alt: String,
+    pub caption: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MediaDescription {
+    pub alt: String,
+    pub caption: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct Media {
+    pub id: u32,
+    pub file_name: String,
+    pub original_name: String,
+    pub mime: String,
+    pub size: i64,
+    pub alt: String,
+    pub caption: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub uses: i64,
+}
+
+impl Media {
+    pub async fn list(db: &SqlitePool, search: &str) -> Result<Vec<Self>, Error> {
+        let pattern = search_pattern(search);
+        query_as::<_, Media>(&format!(
+            "{} WHERE {} ORDER BY created_at desc, id desc",
+            select(),
+            SEARCH
+        ))
+        .bind(pattern)
+        .fetch_all(db)
+        .await
+    }
+
+    pub async fn page(
+        db: &SqlitePool,
+        search: &str,
+        pagination: Pagination,
+    ) -> Result<Page<Self>, Error> {
+        let pattern = search_pattern(search);
+        let items = query_as::<_, Media>(&format!(
+            "{} WHERE {} ORDER BY created_at desc, id desc LIMIT ?2 OFFSET ?3",
+            select(),
+            SEARCH
+        ))
+        .bind(&pattern)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(db)
+        .await?;
+        let (total,): (i64,) = query_as(&format!("SELECT count(*) FROM media WHERE {}", SEARCH))
+            .bind(&pattern)
+            .fetch_one(db)
+            .await?;
+        Ok(Page::new(items, pagination, total))
+    }
+
+    pub async fn fetch(db: &SqlitePool, id: u32) -> Result<Self, Error> {
+        query_as::<_, Media>(&format!("{} WHERE id = ?", select()))
+            .bind(id)
+            .fetch_one(db)
+            .await
+    }
+
+    pub async fn fetch_by_file_name(db: &SqlitePool, file_name: &str) -> Result<Self, Error> {
+        query_as::<_, Media>(&format!("{} WHERE file_name = ?", select()))
+            .bind(file_name)
+            .fetch_one(db)
+            .await
+    }
+
+    // Covers and library images are named by content, so one file can be
+    // a cover of several posts, an image in texts and a library entry at
+    // once; it may only be removed when nothing uses it any more.
+    pub async fn in_use(db: &SqlitePool, file_name: &str) -> Result<bool, Error> {
+        let (count,): (i64,) = query_as(&format!(
+            "SELECT (SELECT count(*) FROM media WHERE file_name = ?1) + {}",
+            USES.replace("{file}", "?1")
+        ))
+        .bind(file_name)
+        .fetch_one(db)
+        .await?;
+        Ok(count > 0)
+    }
+
+    // Uploading the same bytes twice yields the same file name, in which
+    // case the existing entry is returned untouched.
+    pub async fn create(db: &SqlitePool, new_media: NewMedia) -> Result<Self, Error> {
+        query(
+            "INSERT into media (file_name, original_name, mime, size, alt, caption) \
+             values (?, ?, ?, ?, ?, ?) ON CONFLICT(file_name) DO NOTHING",
+        )
+        .bind(&new_media.file_name)
+        .bind(new_media.original_name)
+        .bind(new_media.mime)
+        .bind(new_media.size)
+        .bind(new_media.alt)
+        .bind(new_media.caption)
+        .execute(db)
+        .await?;
+        Self::fetch_by_file_name(db, &new_media.file_name).await
+    }
+
+    // Files are named by their content, so the name identifies an entry
+    // across databases better than the id does.
+    pub async fn upsert(
+        tx: &mut Transaction<'_, Sqlite>,
+        media: &Media,
+    ) -> Result<SqliteQueryResult, Error> {
+        query(
+            "INSERT into media (file_name, original_name, mime, size, alt, caption, created_at) \
+             values (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT(file_name) DO UPDATE SET original_name = excluded.original_name, \
+             mime = excluded.mime, size = excluded.size, alt = excluded.alt, \
+             caption = excluded.caption",
+        )
+        .bind(&media.file_name)
+        .bind(&media.original_name)
+        .bind(&media.mime)
+        .bind(media.size)
+        .bind(&media.alt)
+        .bind(&media.caption)
+        .bind(media.created_at)
+        .execute(&mut *tx)
+        .await
+    }
+
+    pub async fn update(
+        db: &SqlitePool,
+        id: u32,
+        description: MediaDescription,
+    ) -> Result<SqliteQueryResult, Error> {
+        query("UPDATE media SET alt = ?, caption = ? WHERE id = ?")
+            .bind(description.alt)
+            .bind(description.caption)
+            .bind(id)
+            .execute(db)
+            .await
+    }
+
+    pub async fn delete(db: &SqlitePool, id: u32) -> Result<SqliteQueryResult, Error> {
+        query("DELETE FROM media WHERE id = ?")
+            .bind(id)
+            .execute(db)
+            .await
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.mime.starts_with("image/") && self.mime != "image/vnd.djvu"
+    }
+
+    pub fn has_variants(&self) -> bool {
+        matches!(
+            self.mime.as_str(),
+            "image/jpeg" | "image/png" | "image/webp"
+        )
+    }
+
+    pub fn url(&self) -> String {
+        format!("/static/uploads/{}", self.file_name)
+    }
+
+    pub fn thumbnail(&self) -> String {
+        if self.has_variants() {
+            format!(
+                "/static/uploads/{}",
+                images::variant_name(&self.file_name, "thumb", "jpg")
+            )
+        } else {
+            self.url()
+        }
+    }
+
+    pub fn size_kb(&self) -> i64 {
+        (self.size + 1023) / 1024
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models;
+
+    #[tokio::test]
+    async fn search_takes_wildcards_literally() {
+        let db = models::memory().await;
+        for (file_name, original_name) in [("a.pdf", "100%_final.pdf"), ("b.pdf", "1000 final.pdf")]
+        {
+            let media = NewMedia {
+                file_name: file_name.to_string(),
+                original_name: original_name.to_string(),
+                mime: "application/pdf".to_string(),
+                size: 1,
+                alt: String::new(),
+                caption: String::new(),
+            };
+            Media::create(&db, media).await.unwrap();
+        }
+        let names = |found: Vec<Media>| -> Vec<String> {
+            found.into_iter().map(|media| media.file_name).collect()
+        };
+        assert_eq!(
+            names(Media::list(&db, "0%_f").await.unwrap()),
+            vec!["a.pdf"]
+        );
+        assert_eq!(names(Media::list(&db, "_").await.unwrap()), vec!["a.pdf"]);
+        let page = Media::page(&db, "%", Pagination::default()).await.unwrap();
+        assert_eq!(
+            (names(page.items), page.total),
+            (vec!["a.pdf".to_string()], 1)
+        );
+        assert_eq!(Media::list(&db, "").await.unwrap().len(), 2);
+    }
+}
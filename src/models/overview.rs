@@ -0,0 +1,145 @@
+use chrono::NaiveDateTime;
+use sqlx::{query_as, sqlite::SqlitePool, Error, FromRow};
+
+use crate::models::section::Section;
+
+// The blog and the three lists of the catalog, as (table, label, the
+// column with the title)
+const KINDS: [(&str, &str, &str); 4] = [
+    ("posts", "Школа", "title"),
+    ("books", "Научные книги и статьи", "name"),
+    ("publications", "Публицистика", "name"),
+    ("texts", "Проза и поэзия", "name"),
+];
+
+pub struct Count {
+    pub label: &'static str,
+    // Also the admin path of the list
+    pub table: &'static str,
+    pub total: i64,
+    pub drafts: i64,
+}
+
+// An entry of any kind, linked to its edit form
+#[derive(FromRow)]
+pub struct Item {
+    pub table: String,
+    pub id: u32,
+    pub title: String,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl Item {
+    pub fn label(&self) -> &'static str {
+        KINDS
+            .iter()
+            .find(|(table, _, _)| *table == self.table)
+            .map_or("", |(_, label, _)| label)
+    }
+
+    pub fn edit(&self) -> String {
+        format!("/{}/update/{}", self.table, self.id)
+    }
+
+    // As stored, in UTC
+    pub fn updated(&self) -> String {
+        self.updated_at
+            .map(|at| at.format("%d.%m.%Y %H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(FromRow)]
+pub struct Viewed {
+    pub id: u32,
+    pub title: String,
+    pub views: i64,
+}
+
+pub async fn counts(db: &SqlitePool) -> Result<Vec<Count>, Error> {
+    let mut counts = Vec::new();
+    for (table, label, _) in KINDS {
+        let (total, drafts) = query_as(&format!(
+            "SELECT count(*), coalesce(sum(draft), 0) FROM {}",
+            table
+        ))
+        .fetch_one(db)
+        .await?;
+        counts.push(Count {
+            label,
+            table,
+            total,
+            drafts,
+        });
+    }
+    Ok(counts)
+}
+
+fn items(condition: &str) -> String {
+    KINDS
+        .iter()
+        .map(|(table, _, title)| {
+            format!(
+                "SELECT '{table}' AS \"table\", id, {title} AS title, updated_at \
+                 FROM {table} WHERE {condition}"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ")
+}
+
+pub async fn recent(db: &SqlitePool, limit: u32) -> Result<Vec<Item>, Error> {
+    query_as(&format!(
+        "{} ORDER BY updated_at DESC LIMIT ?",
+        items("updated_at IS NOT NULL")
+    ))
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+// Oldest edits first, as they have waited the longest
+pub async fn drafts(db: &SqlitePool) -> Result<Vec<Item>, Error> {
+    query_as(&format!(
+        "SELECT * FROM ({}) ORDER BY updated_at IS NULL, updated_at",
+        items("draft = 1")
+    ))
+    .fetch_all(db)
+    .await
+}
+
+// From the visits of entry pages, so the admin and bots aren't counted
+pub async fn most_viewed(db: &SqlitePool, limit: u32) -> Result<Vec<Viewed>, Error> {
+    query_as(
+        "SELECT posts.id, posts.title, sum(visits.views) AS views FROM visits \
+         JOIN posts ON visits.path = '/entry/' || posts.id \
+         WHERE visits.kind = 'page' AND posts.draft = 0 \
+         GROUP BY posts.id ORDER BY views DESC, posts.id LIMIT ?",
+    )
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+// Links of catalog entries, for checking them
+pub async fn catalog_links(db: &SqlitePool) -> Result<Vec<(Item, String)>, Error> {
+    let mut links = Vec::new();
+    for section in Section::ALL {
+        let rows: Vec<(u32, String, String, Option<NaiveDateTime>)> = query_as(&format!(
+            "SELECT id, name, link, updated_at FROM {} ORDER BY position, id",
+            section.as_str()
+        ))
+        .fetch_all(db)
+        .await?;
+        for (id, title, link, updated_at) in rows {
+            let item = Item {
+                table: section.as_str().to_string(),
+                id,
+                title,
+                updated_at,
+            };
+            links.push((item, link));
+        }
+    }
+    Ok(links)
+}
@@ -1,9 +1,18 @@
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{
-    query, query_as,
-    sqlite::{SqlitePool, SqliteQueryResult},
-    Error, FromRow,
+    query, query_as, query_scalar,
+    sqlite::{Sqlite, SqlitePool, SqliteQueryResult},
+    Error, FromRow, Transaction,
+};
+
+use crate::{
+    images,
+    markup::Format,
+    models::{
+        term::{Entry, Facets},
+        Page, Pagination,
+    },
 };
 
 #[derive(Deserialize, Debug)]
@@ -11,26 +20,213 @@ pub struct NewPost {
     pub title: String,
     pub lead: String,
     pub body: String,
+    pub format: Format,
     pub cover: String,
+    pub slug: String,
+    // Now when creating, unchanged when updating
+    pub published_at: Option<DateTime<Utc>>,
+    // Published when creating, unchanged when updating
+    pub draft: Option<bool>,
 }
 
-#[derive(FromRow, Clone)]
+#[derive(Serialize, Deserialize, FromRow, Clone)]
 pub struct Post {
     pub id: u32,
     pub title: String,
     pub lead: String,
     pub body: String,
+    pub format: Format,
     pub cover: String,
     pub created_at: DateTime<Utc>,
+    // Exports made before slugs existed don't have one
+    #[serde(default)]
+    pub slug: String,
+    // The date shown to readers, which editors may set to the past; exports
+    // made before it existed fall back to `created_at` on import
+    #[serde(default)]
+    pub published_at: DateTime<Utc>,
+    // Drafts are only seen in the admin
+    #[serde(default)]
+    pub draft: bool,
+}
+
+#[derive(FromRow)]
+pub struct ArchiveMonth {
+    pub year: i32,
+    pub month: u32,
+    pub posts: i64,
+}
+
+impl ArchiveMonth {
+    pub fn name(&self) -> &'static str {
+        month_name(self.month)
+    }
+}
+
+pub struct ArchiveYear {
+    pub year: i32,
+    pub posts: i64,
+    pub months: Vec<ArchiveMonth>,
+}
+
+const MONTHS: [&str; 12] = [
+    "Январь",
+    "Февраль",
+    "Март",
+    "Апрель",
+    "Май",
+    "Июнь",
+    "Июль",
+    "Август",
+    "Сентябрь",
+    "Октябрь",
+    "Ноябрь",
+    "Декабрь",
+];
+
+pub fn month_name(month: u32) -> &'static str {
+    MONTHS
+        .get((month as usize).wrapping_sub(1))
+        .copied()
+        .unwrap_or("")
+}
+
+// The format CURRENT_TIMESTAMP writes, so dates compare and sort as text
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
 impl Post {
+    pub fn cover_variant(&self, variant: &str, extension: &str) -> String {
+        images::variant_name(&self.cover, variant, extension)
+    }
+
     pub async fn list(db: &SqlitePool) -> Result<Vec<Self>, Error> {
-        query_as::<_, Post>("SELECT * FROM posts ORDER BY created_at desc")
+        query_as::<_, Post>("SELECT * FROM posts ORDER BY published_at desc")
             .fetch_all(db)
             .await
     }
 
+    pub async fn page(db: &SqlitePool, pagination: Pagination) -> Result<Page<Self>, Error> {
+        let posts = query_as::<_, Post>(
+            "SELECT * FROM posts ORDER BY published_at desc, id desc LIMIT ? OFFSET ?",
+        )
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(db)
+        .await?;
+        let (total,): (i64,) = query_as("SELECT count(*) FROM posts").fetch_one(db).await?;
+        Ok(Page::new(posts, pagination, total))
+    }
+
+    pub async fn page_faceted(
+        db: &SqlitePool,
+        facets: &Facets,
+        pagination: Pagination,
+    ) -> Result<Page<Self>, Error> {
+        let condition = format!("draft = 0 AND {}", Facets::condition(Entry::Post, "posts"));
+        let sql = format!(
+            "SELECT * FROM posts WHERE {} ORDER BY published_at desc, id desc LIMIT ? OFFSET ?",
+            condition
+        );
+        let mut posts = query_as::<_, Post>(&sql);
+        for value in facets.binds() {
+            posts = posts.bind(value);
+        }
+        let posts = posts
+            .bind(pagination.limit())
+            .bind(pagination.offset())
+            .fetch_all(db)
+            .await?;
+        let sql = format!("SELECT count(*) FROM posts WHERE {}", condition);
+        let mut total = query_as::<_, (i64,)>(&sql);
+        for value in facets.binds() {
+            total = total.bind(value);
+        }
+        let (total,) = total.fetch_one(db).await?;
+        Ok(Page::new(posts, pagination, total))
+    }
+
+    // A year, or a month of it when `month` is given
+    pub async fn page_dated(
+        db: &SqlitePool,
+        year: i32,
+        month: Option<u32>,
+        pagination: Pagination,
+    ) -> Result<Page<Self>, Error> {
+        let condition = "draft = 0 AND strftime('%Y', published_at) = ?1 \
+                         AND (?2 = 0 OR CAST(strftime('%m', published_at) AS integer) = ?2)";
+        let year = format!("{:04}", year);
+        let month = month.unwrap_or(0);
+        let posts = query_as::<_, Post>(&format!(
+            "SELECT * FROM posts WHERE {} ORDER BY published_at desc, id desc LIMIT ?3 OFFSET ?4",
+            condition
+        ))
+        .bind(&year)
+        .bind(month)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(db)
+        .await?;
+        let (total,): (i64,) = query_as(&format!("SELECT count(*) FROM posts WHERE {}", condition))
+            .bind(&year)
+            .bind(month)
+            .fetch_one(db)
+            .await?;
+        Ok(Page::new(posts, pagination, total))
+    }
+
+    // Newest first, with the number of posts in every month that has any
+    pub async fn archive(db: &SqlitePool) -> Result<Vec<ArchiveYear>, Error> {
+        let months = query_as::<_, ArchiveMonth>(
+            "SELECT CAST(strftime('%Y', published_at) AS integer) AS year, \
+             CAST(strftime('%m', published_at) AS integer) AS month, count(*) AS posts \
+             FROM posts WHERE draft = 0 GROUP BY year, month ORDER BY year desc, month desc",
+        )
+        .fetch_all(db)
+        .await?;
+        let mut years: Vec<ArchiveYear> = Vec::new();
+        for month in months {
+            match years.last_mut() {
+                Some(year) if year.year == month.year => {
+                    year.posts += month.posts;
+                    year.months.push(month);
+                }
+                _ => years.push(ArchiveYear {
+                    year: month.year,
+                    posts: month.posts,
+                    months: vec![month],
+                }),
+            }
+        }
+        Ok(years)
+    }
+
+    pub async fn page_tagged(
+        db: &SqlitePool,
+        tag_id: u32,
+        pagination: Pagination,
+    ) -> Result<Page<Self>, Error> {
+        let posts = query_as::<_, Post>(
+            "SELECT posts.* FROM posts JOIN post_tags ON post_tags.post_id = posts.id \
+             WHERE post_tags.tag_id = ? AND posts.draft = 0 \
+             ORDER BY published_at desc, id desc LIMIT ? OFFSET ?",
+        )
+        .bind(tag_id)
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(db)
+        .await?;
+        let (total,): (i64,) = query_as(
+            "SELECT count(*) FROM post_tags JOIN posts ON posts.id = post_tags.post_id \
+             WHERE post_tags.tag_id = ? AND posts.draft = 0",
+        )
+        .bind(tag_id)
+        .fetch_one(db)
+        .await?;
+        Ok(Page::new(posts, pagination, total))
+    }
+
     pub async fn fetch(db: &SqlitePool, id: u32) -> Result<Self, Error> {
         query_as::<_, Post>("SELECT * FROM posts WHERE id = ?")
             .bind(id)
@@ -38,29 +234,121 @@ impl Post {
             .await
     }
 
-    pub async fn create(db: &SqlitePool, new_post: NewPost) -> Result<SqliteQueryResult, Error> {
-        query("INSERT into posts (title, lead, body, cover) values (?, ?, ?, ?)")
-            .bind(new_post.title)
-            .bind(new_post.lead)
-            .bind(new_post.body)
-            .bind(new_post.cover)
-            .execute(db)
+    pub async fn fetch_by_slug(db: &SqlitePool, slug: &str) -> Result<Self, Error> {
+        query_as::<_, Post>("SELECT * FROM posts WHERE slug = ?")
+            .bind(slug)
+            .fetch_one(db)
             .await
     }
 
+    pub async fn slug_taken(db: &SqlitePool, slug: &str, except: u32) -> Result<bool, Error> {
+        let (count,): (i64,) = query_as("SELECT count(*) FROM posts WHERE slug = ? AND id != ?")
+            .bind(slug)
+            .bind(except)
+            .fetch_one(db)
+            .await?;
+        Ok(count > 0)
+    }
+
+    pub async fn create(db: &SqlitePool, new_post: NewPost) -> Result<SqliteQueryResult, Error> {
+        let mut tx = db.begin().await?;
+        let result = Self::create_in(&mut tx, new_post).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    pub async fn create_in(
+        tx: &mut Transaction<'_, Sqlite>,
+        new_post: NewPost,
+    ) -> Result<SqliteQueryResult, Error> {
+        query(
+            "INSERT into posts (title, lead, body, format, cover, slug, published_at, draft) \
+             values (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(new_post.title)
+        .bind(new_post.lead)
+        .bind(new_post.body)
+        .bind(new_post.format)
+        .bind(new_post.cover)
+        .bind(new_post.slug)
+        .bind(timestamp(new_post.published_at.unwrap_or_else(Utc::now)))
+        .bind(new_post.draft.unwrap_or(false))
+        .execute(&mut *tx)
+        .await
+    }
+
     pub async fn update(
         db: &SqlitePool,
         id: u32,
         updated_post: NewPost,
     ) -> Result<SqliteQueryResult, Error> {
-        query("UPDATE posts SET title = ?, lead = ?, body = ?, cover = ? WHERE id = ?")
-            .bind(updated_post.title)
-            .bind(updated_post.lead)
-            .bind(updated_post.body)
-            .bind(updated_post.cover)
-            .bind(id)
-            .execute(db)
-            .await
+        let mut tx = db.begin().await?;
+        let result = Self::update_in(&mut tx, id, updated_post).await?;
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    pub async fn update_in(
+        tx: &mut Transaction<'_, Sqlite>,
+        id: u32,
+        updated_post: NewPost,
+    ) -> Result<SqliteQueryResult, Error> {
+        query(
+            "UPDATE posts SET title = ?, lead = ?, body = ?, format = ?, cover = ?, slug = ?, \
+             published_at = coalesce(?, published_at), draft = coalesce(?, draft) WHERE id = ?",
+        )
+        .bind(updated_post.title)
+        .bind(updated_post.lead)
+        .bind(updated_post.body)
+        .bind(updated_post.format)
+        .bind(updated_post.cover)
+        .bind(updated_post.slug)
+        .bind(updated_post.published_at.map(timestamp))
+        .bind(updated_post.draft)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+    }
+
+    pub async fn referencing(db: &SqlitePool, file_name: &str) -> Result<Vec<Self>, Error> {
+        query_as::<_, Post>(
+            "SELECT * FROM posts WHERE cover = ?1 OR instr(body, ?1) > 0 ORDER BY published_at desc",
+        )
+        .bind(file_name)
+        .fetch_all(db)
+        .await
+    }
+
+    // Matched by slug, since ids differ between sites; returns the id the post
+    // has here, which is a new one unless the slug was already taken.
+    pub async fn upsert(tx: &mut Transaction<'_, Sqlite>, post: &Post) -> Result<u32, Error> {
+        query_scalar(
+            "INSERT into posts (title, lead, body, format, cover, created_at, slug, published_at, \
+             draft) values (?, ?, ?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT(slug) DO UPDATE SET title = excluded.title, lead = excluded.lead, \
+             body = excluded.body, format = excluded.format, cover = excluded.cover, \
+             created_at = excluded.created_at, published_at = excluded.published_at, \
+             draft = excluded.draft RETURNING id",
+        )
+        .bind(&post.title)
+        .bind(&post.lead)
+        .bind(&post.body)
+        .bind(post.format)
+        .bind(&post.cover)
+        .bind(post.created_at)
+        .bind(match post.slug.is_empty() {
+            true => format!("post-{}", post.id),
+            false => post.slug.clone(),
+        })
+        .bind(timestamp(
+            match post.published_at == DateTime::<Utc>::default() {
+                true => post.created_at,
+                false => post.published_at,
+            },
+        ))
+        .bind(post.draft)
+        .fetch_one(&mut *tx)
+        .await
     }
 
     pub async fn delete(db: &SqlitePool, id: u32) -> Result<SqliteQueryResult, Error> {
@@ -69,4 +357,82 @@ impl Post {
             .execute(db)
             .await
     }
+
+    // Returns the deleted posts, whose covers may need removing
+    pub async fn delete_many(db: &SqlitePool, ids: &[u32]) -> Result<Vec<Self>, Error> {
+        let mut tx = db.begin().await?;
+        let mut deleted = Vec::new();
+        for id in ids {
+            let post = query_as::<_, Post>("SELECT * FROM posts WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if let Some(post) = post {
+                query("DELETE FROM posts WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                deleted.push(post);
+            }
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    pub async fn set_draft(db: &SqlitePool, ids: &[u32], draft: bool) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+        for id in ids {
+            query("UPDATE posts SET draft = ? WHERE id = ?")
+                .bind(draft)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models;
+
+    // The last post can't be deleted or changed, so a bulk action fails
+    // after the others are done
+    async fn posts() -> SqlitePool {
+        let db = models::memory().await;
+        for id in 1..=3 {
+            query("INSERT INTO posts (id, title, lead, body, cover, slug) VALUES (?, '', '', '', '', ?)")
+                .bind(id)
+                .bind(format!("post-{}", id))
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        for trigger in [
+            "fail_delete BEFORE DELETE ON posts WHEN OLD.id = 3",
+            "fail_update BEFORE UPDATE ON posts WHEN OLD.id = 3",
+        ] {
+            query(&format!(
+                "CREATE TRIGGER {} BEGIN SELECT RAISE(ABORT, 'fail'); END",
+                trigger
+            ))
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn failed_bulk_action_changes_nothing() {
+        let db = posts().await;
+        assert!(Post::set_draft(&db, &[1, 2, 3], true).await.is_err());
+        assert!(Post::delete_many(&db, &[1, 2, 3]).await.is_err());
+        let drafts: Vec<(u32, bool)> = query_as("SELECT id, draft FROM posts ORDER BY id")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(drafts, [(1, false), (2, false), (3, false)]);
+    }
 }
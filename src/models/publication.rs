@@ -1,32 +1,83 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{
-    query, query_as,
-    sqlite::{SqlitePool, SqliteQueryResult},
-    Error, FromRow,
+    query, query_as, query_scalar,
+    sqlite::{Sqlite, SqlitePool, SqliteQueryResult},
+    Error, FromRow, Transaction,
 };
+use utoipa::ToSchema;
 
-#[derive(Deserialize, Debug)]
+use crate::models::{
+    self,
+    term::{Entry, Facets},
+    Page, Pagination,
+};
+
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct NewPublication {
     pub name: String,
     pub link: String,
     pub description: String,
 }
 
-#[derive(FromRow, Clone)]
+#[derive(Serialize, Deserialize, FromRow, Clone, ToSchema)]
 pub struct Publication {
     pub id: u32,
     pub name: String,
     pub link: String,
     pub description: String,
+    // Where the entry goes on the home page; exports made before it
+    // existed keep the order of ids
+    #[serde(default)]
+    pub position: i64,
+    // Drafts are only seen in the admin
+    #[serde(default)]
+    pub draft: bool,
+    // Followed links from the site, see `Section::download`
+    #[serde(default)]
+    pub downloads: i64,
 }
 
 impl Publication {
     pub async fn list(db: &SqlitePool) -> Result<Vec<Self>, Error> {
-        query_as::<_, Publication>("SELECT * FROM publications ORDER BY id")
+        query_as::<_, Publication>("SELECT * FROM publications ORDER BY position, id")
             .fetch_all(db)
             .await
     }
 
+    pub async fn faceted(db: &SqlitePool, facets: &Facets) -> Result<Vec<Self>, Error> {
+        let sql = format!(
+            "SELECT * FROM publications WHERE draft = 0 AND {} ORDER BY position, id",
+            Facets::condition(Entry::Publication, "publications")
+        );
+        let mut items = query_as::<_, Publication>(&sql);
+        for value in facets.binds() {
+            items = items.bind(value);
+        }
+        items.fetch_all(db).await
+    }
+
+    pub async fn published(db: &SqlitePool) -> Result<Vec<Self>, Error> {
+        query_as::<_, Publication>(
+            "SELECT * FROM publications WHERE draft = 0 ORDER BY position, id",
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    pub async fn page(db: &SqlitePool, pagination: Pagination) -> Result<Page<Self>, Error> {
+        let items = query_as::<_, Publication>(
+            "SELECT * FROM publications ORDER BY position, id LIMIT ? OFFSET ?",
+        )
+        .bind(pagination.limit())
+        .bind(pagination.offset())
+        .fetch_all(db)
+        .await?;
+        let (total,): (i64,) = query_as("SELECT count(*) FROM publications")
+            .fetch_one(db)
+            .await?;
+        Ok(Page::new(items, pagination, total))
+    }
+
     pub async fn fetch(db: &SqlitePool, id: u32) -> Result<Self, Error> {
         query_as::<_, Publication>("SELECT * FROM publications WHERE id = ?")
             .bind(id)
@@ -38,12 +89,15 @@ impl Publication {
         db: &SqlitePool,
         new_publication: NewPublication,
     ) -> Result<SqliteQueryResult, Error> {
-        query("INSERT into publications (name, link, description) values (?, ?, ?)")
-            .bind(new_publication.name)
-            .bind(new_publication.link)
-            .bind(new_publication.description)
-            .execute(db)
-            .await
+        query(
+            "INSERT into publications (name, link, description, position) \
+             values (?, ?, ?, (SELECT coalesce(max(position), 0) + 1 FROM publications))",
+        )
+        .bind(new_publication.name)
+        .bind(new_publication.link)
+        .bind(new_publication.description)
+        .execute(db)
+        .await
     }
 
     pub async fn update(
@@ -60,6 +114,42 @@ impl Publication {
             .await
     }
 
+    // Matched by link, since ids differ between sites; returns the id the
+    // entry has here, which is a new one unless the link is already listed.
+    pub async fn upsert(
+        tx: &mut Transaction<'_, Sqlite>,
+        publication: &Publication,
+    ) -> Result<u32, Error> {
+        let id = models::id_by_link(tx, "publications", &publication.link).await?;
+        query_scalar(
+            "INSERT into publications (id, name, link, description, position, draft, downloads) \
+             values (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, link = excluded.link, \
+             description = excluded.description, position = excluded.position, \
+             draft = excluded.draft, downloads = excluded.downloads RETURNING id",
+        )
+        .bind(id)
+        .bind(&publication.name)
+        .bind(&publication.link)
+        .bind(&publication.description)
+        .bind(match publication.position {
+            0 => publication.id as i64,
+            position => position,
+        })
+        .bind(publication.draft)
+        .bind(publication.downloads)
+        .fetch_one(&mut *tx)
+        .await
+    }
+
+    pub async fn ids(db: &SqlitePool) -> Result<Vec<u32>, Error> {
+        models::ordered_ids(db, "publications").await
+    }
+
+    pub async fn reorder(db: &SqlitePool, ids: &[u32]) -> Result<bool, Error> {
+        models::reorder(db, "publications", ids).await
+    }
+
     pub async fn delete(db: &SqlitePool, id: u32) -> Result<SqliteQueryResult, Error> {
         query("DELETE FROM publications WHERE id = ?")
             .bind(id)
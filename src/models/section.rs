@@ -0,0 +1,218 @@
+use serde::Deserialize;
+use sqlx::{query, query_scalar, sqlite::SqlitePool, Error};
+
+use crate::models::term::Entry;
+
+// The three lists of the catalog share their columns, so an entry filed
+// under the wrong one can be moved rather than retyped.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Section {
+    Books,
+    Publications,
+    Texts,
+}
+
+impl Section {
+    pub const ALL: [Section; 3] = [Section::Books, Section::Publications, Section::Texts];
+
+    // Also the admin path of the list
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Section::Books => "books",
+            Section::Publications => "publications",
+            Section::Texts => "texts",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Section::Books => "Научные книги и статьи",
+            Section::Publications => "Публицистика",
+            Section::Texts => "Проза и поэзия",
+        }
+    }
+
+    // Texts aren't classified
+    fn entry(&self) -> Option<Entry> {
+        match self {
+            Section::Books => Some(Entry::Book),
+            Section::Publications => Some(Entry::Publication),
+            Section::Texts => None,
+        }
+    }
+
+    pub async fn delete(&self, db: &SqlitePool, ids: &[u32]) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+        for id in ids {
+            query(&format!("DELETE FROM {} WHERE id = ?", self.as_str()))
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn set_draft(&self, db: &SqlitePool, ids: &[u32], draft: bool) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+        for id in ids {
+            query(&format!(
+                "UPDATE {} SET draft = ? WHERE id = ?",
+                self.as_str()
+            ))
+            .bind(draft)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    // Moved entries get new ids and go to the end of the other list; their
+    // terms come along when both lists are classified.
+    pub async fn move_to(
+        &self,
+        db: &SqlitePool,
+        ids: &[u32],
+        target: Section,
+    ) -> Result<(), Error> {
+        if *self == target {
+            return Ok(());
+        }
+        let mut tx = db.begin().await?;
+        for id in ids {
+            let inserted = query(&format!(
+                "INSERT into {target} (name, link, description, draft, downloads, position) \
+                 SELECT name, link, description, draft, downloads, \
+                 (SELECT coalesce(max(position), 0) + 1 FROM {target}) FROM {source} WHERE id = ?",
+                target = target.as_str(),
+                source = self.as_str(),
+            ))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            if inserted.rows_affected() == 0 {
+                continue;
+            }
+            if let (Some(from), Some(to)) = (self.entry(), target.entry()) {
+                query(&format!(
+                    "INSERT into {} ({}, term_id) SELECT ?, term_id FROM {} WHERE {} = ?",
+                    to.table(),
+                    to.column(),
+                    from.table(),
+                    from.column()
+                ))
+                .bind(inserted.last_insert_rowid())
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            query(&format!("DELETE FROM {} WHERE id = ?", self.as_str()))
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    // The link of a published entry, which the site leads to through
+    // /download so that following it is counted
+    pub async fn download(&self, db: &SqlitePool, id: u32) -> Result<String, Error> {
+        query_scalar(&format!(
+            "SELECT link FROM {} WHERE id = ? AND draft = 0 AND link != ''",
+            self.as_str()
+        ))
+        .bind(id)
+        .fetch_one(db)
+        .await
+    }
+
+    pub async fn count_download(&self, db: &SqlitePool, id: u32) -> Result<(), Error> {
+        query(&format!(
+            "UPDATE {} SET downloads = downloads + 1 WHERE id = ?",
+            self.as_str()
+        ))
+        .bind(id)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models, Book};
+
+    async fn books() -> SqlitePool {
+        let db = models::memory().await;
+        for (id, name) in [(1, "Первая"), (2, "Вторая"), (3, "Третья")] {
+            query("INSERT INTO books (id, name, link, description, position) VALUES (?, ?, '', '', ?)")
+                .bind(id)
+                .bind(name)
+                .bind(id)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        db
+    }
+
+    // The last book can't be deleted, changed or copied, so a bulk action
+    // fails after the others are done
+    async fn break_last(db: &SqlitePool) {
+        for trigger in [
+            "fail_delete BEFORE DELETE ON books WHEN OLD.id = 3",
+            "fail_update BEFORE UPDATE ON books WHEN OLD.id = 3",
+            "fail_insert BEFORE INSERT ON publications WHEN NEW.name = 'Третья'",
+        ] {
+            query(&format!(
+                "CREATE TRIGGER {} BEGIN SELECT RAISE(ABORT, 'fail'); END",
+                trigger
+            ))
+            .execute(db)
+            .await
+            .unwrap();
+        }
+    }
+
+    async fn state(db: &SqlitePool) -> Vec<(u32, bool, i64)> {
+        sqlx::query_as("SELECT id, draft, position FROM books ORDER BY id")
+            .fetch_all(db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn stale_or_partial_ordering_changes_nothing() {
+        let db = books().await;
+        let before = state(&db).await;
+        for ids in [vec![3, 1], vec![3, 2, 1, 4], vec![3, 3, 1], vec![]] {
+            assert!(!Book::reorder(&db, &ids).await.unwrap());
+            assert_eq!(state(&db).await, before);
+        }
+        assert!(Book::reorder(&db, &[3, 1, 2]).await.unwrap());
+        assert_eq!(models::ordered_ids(&db, "books").await.unwrap(), [3, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn failed_bulk_action_changes_nothing() {
+        let db = books().await;
+        break_last(&db).await;
+        let before = state(&db).await;
+        let section = Section::Books;
+        assert!(Book::reorder(&db, &[2, 1, 3]).await.is_err());
+        assert!(section.delete(&db, &[1, 2, 3]).await.is_err());
+        assert!(section.set_draft(&db, &[1, 2, 3], true).await.is_err());
+        assert!(section
+            .move_to(&db, &[1, 2, 3], Section::Publications)
+            .await
+            .is_err());
+        assert_eq!(state(&db).await, before);
+        let moved: i64 = query_scalar("SELECT count(*) FROM publications")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(moved, 0);
+    }
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    query, query_as,
+    sqlite::{Sqlite, SqlitePool},
+    Error, FromRow, Transaction,
+};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
+pub struct NewSource {
+    pub author: String,
+    pub title: String,
+    pub year: String,
+    pub archive: String,
+    pub fond: String,
+    pub opis: String,
+    pub delo: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct Source {
+    pub id: u32,
+    pub post_id: u32,
+    pub position: u32,
+    pub author: String,
+    pub title: String,
+    pub year: String,
+    pub archive: String,
+    pub fond: String,
+    pub opis: String,
+    pub delo: String,
+    pub url: String,
+}
+
+impl NewSource {
+    pub fn is_empty(&self) -> bool {
+        [
+            &self.author,
+            &self.title,
+            &self.year,
+            &self.archive,
+            &self.fond,
+            &self.opis,
+            &self.delo,
+            &self.url,
+        ]
+        .iter()
+        .all(|field| field.trim().is_empty())
+    }
+}
+
+impl From<Source> for NewSource {
+    fn from(source: Source) -> Self {
+        Self {
+            author: source.author,
+            title: source.title,
+            year: source.year,
+            archive: source.archive,
+            fond: source.fond,
+            opis: source.opis,
+            delo: source.delo,
+            url: source.url,
+        }
+    }
+}
+
+impl Source {
+    pub async fn list(db: &SqlitePool, post_id: u32) -> Result<Vec<Self>, Error> {
+        query_as::<_, Source>("SELECT * FROM sources WHERE post_id = ? ORDER BY position")
+            .bind(post_id)
+            .fetch_all(db)
+            .await
+    }
+
+    pub async fn replace(
+        db: &SqlitePool,
+        post_id: u32,
+        sources: Vec<NewSource>,
+    ) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+        Self::replace_in(&mut tx, post_id, sources).await?;
+        tx.commit().await
+    }
+
+    pub async fn replace_in(
+        tx: &mut Transaction<'_, Sqlite>,
+        post_id: u32,
+        sources: Vec<NewSource>,
+    ) -> Result<(), Error> {
+        query("DELETE FROM sources WHERE post_id = ?")
+            .bind(post_id)
+            .execute(&mut *tx)
+            .await?;
+        for (position, source) in sources.into_iter().enumerate() {
+            query(
+                "INSERT into sources (post_id, position, author, title, year, archive, fond, opis, delo, url) \
+                 values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(post_id)
+            .bind(position as u32)
+            .bind(source.author)
+            .bind(source.title)
+            .bind(source.year)
+            .bind(source.archive)
+            .bind(source.fond)
+            .bind(source.opis)
+            .bind(source.delo)
+            .bind(source.url)
+            .execute(&mut *tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub fn is_archival(&self) -> bool {
+        !self.archive.is_empty()
+    }
+
+    // Archival references follow the usual "Архив. Ф. 1. Оп. 2. Д. 3." form,
+    // everything else is cited as "Автор. Название. — Год."
+    pub fn citation(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.author.is_empty() {
+            parts.push(self.author.clone());
+        }
+        if !self.title.is_empty() {
+            parts.push(self.title.clone());
+        }
+        if self.is_archival() {
+            parts.push(self.archive.clone());
+            for (abbreviation, value) in
+                [("Ф.", &self.fond), ("Оп.", &self.opis), ("Д.", &self.delo)]
+            {
+                if !value.is_empty() {
+                    parts.push(format!("{} {}", abbreviation, value));
+                }
+            }
+        }
+        let mut citation = parts
+            .iter()
+            .map(|part| part.trim_end_matches('.'))
+            .collect::<Vec<_>>()
+            .join(". ");
+        if !self.year.is_empty() {
+            citation.push_str(&format!(". — {}", self.year));
+        }
+        if !citation.is_empty() {
+            citation.push('.');
+        }
+        citation
+    }
+
+    pub fn bibtex(&self) -> String {
+        let mut fields = Vec::new();
+        for (name, value) in [
+            ("author", &self.author),
+            ("title", &self.title),
+            ("year", &self.year),
+            ("url", &self.url),
+        ] {
+            if !value.is_empty() {
+                fields.push((name, value.clone()));
+            }
+        }
+        if self.is_archival() {
+            let archival = Source {
+                author: String::new(),
+                title: String::new(),
+                year: String::new(),
+                ..self.clone()
+            };
+            fields.push(("howpublished", archival.citation()));
+        }
+        let body = fields
+            .iter()
+            .map(|(name, value)| match *name {
+                "url" => format!("  {} = {{{}}}", name, value),
+                _ => format!("  {} = {{{}}}", name, escape_bibtex(value)),
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!(
+            "@misc{{post{}_source{},\n{}\n}}\n",
+            self.post_id,
+            self.position + 1,
+            body
+        )
+    }
+}
+
+fn escape_bibtex(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '%' | '&' | '#' | '_' | '$' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+        escaped
+    })
+}
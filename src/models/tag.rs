@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    query, query_as,
+    sqlite::{Sqlite, SqlitePool},
+    Error, FromRow, Transaction,
+};
+use utoipa::ToSchema;
+
+use crate::slug;
+
+#[derive(Serialize, Deserialize, FromRow, Clone, ToSchema)]
+pub struct Tag {
+    pub id: u32,
+    pub name: String,
+    pub slug: String,
+}
+
+#[derive(FromRow)]
+pub struct TagCount {
+    pub name: String,
+    pub slug: String,
+    pub posts: i64,
+    // From 1 for the rarest to 5 for the most used tag of the cloud
+    #[sqlx(default)]
+    pub weight: i64,
+}
+
+// "Гражданская война,  Сибирь, сибирь" is two tags.
+pub fn parse(names: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for name in names.split(',') {
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !name.is_empty()
+            && !tags
+                .iter()
+                .any(|tag| tag.to_lowercase() == name.to_lowercase())
+        {
+            tags.push(name);
+        }
+    }
+    tags
+}
+
+impl Tag {
+    pub async fn list(db: &SqlitePool) -> Result<Vec<Self>, Error> {
+        query_as::<_, Tag>("SELECT * FROM tags ORDER BY name")
+            .fetch_all(db)
+            .await
+    }
+
+    // Only tags that have published posts
+    pub async fn cloud(db: &SqlitePool) -> Result<Vec<TagCount>, Error> {
+        let mut cloud = query_as::<_, TagCount>(
+            "SELECT tags.name, tags.slug, count(*) AS posts FROM tags \
+             JOIN post_tags ON post_tags.tag_id = tags.id \
+             JOIN posts ON posts.id = post_tags.post_id WHERE posts.draft = 0 \
+             GROUP BY tags.id ORDER BY tags.name",
+        )
+        .fetch_all(db)
+        .await?;
+        let (min, max) = cloud.iter().fold((i64::MAX, 0), |(min, max), tag| {
+            (min.min(tag.posts), max.max(tag.posts))
+        });
+        for tag in cloud.iter_mut() {
+            tag.weight = 1 + (tag.posts - min) * 4 / (max - min).max(1);
+        }
+        Ok(cloud)
+    }
+
+    pub async fn fetch_by_slug(db: &SqlitePool, slug: &str) -> Result<Self, Error> {
+        query_as::<_, Tag>("SELECT * FROM tags WHERE slug = ?")
+            .bind(slug)
+            .fetch_one(db)
+            .await
+    }
+
+    pub async fn for_post(db: &SqlitePool, post_id: u32) -> Result<Vec<Self>, Error> {
+        query_as::<_, Tag>(
+            "SELECT tags.* FROM tags JOIN post_tags ON post_tags.tag_id = tags.id \
+             WHERE post_tags.post_id = ? ORDER BY tags.name",
+        )
+        .bind(post_id)
+        .fetch_all(db)
+        .await
+    }
+
+    pub async fn replace(db: &SqlitePool, post_id: u32, names: Vec<String>) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+        Self::replace_in(&mut tx, post_id, names).await?;
+        tx.commit().await
+    }
+
+    // Tags are matched by name regardless of case and created when new;
+    // tags no post uses any more are removed.
+    pub async fn replace_in(
+        tx: &mut Transaction<'_, Sqlite>,
+        post_id: u32,
+        names: Vec<String>,
+    ) -> Result<(), Error> {
+        query("DELETE FROM post_tags WHERE post_id = ?")
+            .bind(post_id)
+            .execute(&mut *tx)
+            .await?;
+        let mut tags = query_as::<_, Tag>("SELECT * FROM tags")
+            .fetch_all(&mut *tx)
+            .await?;
+        for name in names {
+            let tag_id = find_or_create(tx, &mut tags, name).await?;
+            link(tx, post_id, tag_id).await?;
+        }
+        remove_unused(tx).await
+    }
+
+    // Adds the tags to every post, keeping the ones they already have
+    pub async fn attach(
+        db: &SqlitePool,
+        post_ids: &[u32],
+        names: Vec<String>,
+    ) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+        let mut tags = query_as::<_, Tag>("SELECT * FROM tags")
+            .fetch_all(&mut *tx)
+            .await?;
+        for name in names {
+            let tag_id = find_or_create(&mut tx, &mut tags, name).await?;
+            for post_id in post_ids {
+                link(&mut tx, *post_id, tag_id).await?;
+            }
+        }
+        tx.commit().await
+    }
+
+    pub async fn detach(
+        db: &SqlitePool,
+        post_ids: &[u32],
+        names: Vec<String>,
+    ) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+        let tags = query_as::<_, Tag>("SELECT * FROM tags")
+            .fetch_all(&mut *tx)
+            .await?;
+        for name in names {
+            let Some(tag) = tags
+                .iter()
+                .find(|tag| tag.name.to_lowercase() == name.to_lowercase())
+            else {
+                continue;
+            };
+            for post_id in post_ids {
+                query("DELETE FROM post_tags WHERE post_id = ? AND tag_id = ?")
+                    .bind(post_id)
+                    .bind(tag.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        remove_unused(&mut tx).await?;
+        tx.commit().await
+    }
+}
+
+// Matched by name regardless of case, SQLite's lower() only knows ASCII
+async fn find_or_create(
+    tx: &mut Transaction<'_, Sqlite>,
+    tags: &mut Vec<Tag>,
+    name: String,
+) -> Result<u32, Error> {
+    if let Some(tag) = tags
+        .iter()
+        .find(|tag| tag.name.to_lowercase() == name.to_lowercase())
+    {
+        return Ok(tag.id);
+    }
+    let slug = unique_slug(tags, &name);
+    let id = query("INSERT into tags (name, slug) values (?, ?)")
+        .bind(&name)
+        .bind(&slug)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid() as u32;
+    tags.push(Tag { id, name, slug });
+    Ok(id)
+}
+
+async fn link(tx: &mut Transaction<'_, Sqlite>, post_id: u32, tag_id: u32) -> Result<(), Error> {
+    query("INSERT OR IGNORE into post_tags (post_id, tag_id) values (?, ?)")
+        .bind(post_id)
+        .bind(tag_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+async fn remove_unused(tx: &mut Transaction<'_, Sqlite>) -> Result<(), Error> {
+    query("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM post_tags)")
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+fn unique_slug(tags: &[Tag], name: &str) -> String {
+    let mut base = slug::slugify(name);
+    if base.is_empty() {
+        base = "tag".to_string();
+    }
+    let mut candidate = base.clone();
+    let mut number = 2;
+    while tags.iter().any(|tag| tag.slug == candidate) {
+        candidate = format!("{}-{}", base, number);
+        number += 1;
+    }
+    candidate
+}
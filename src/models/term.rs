@@ -0,0 +1,396 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    query, query_as, query_scalar,
+    sqlite::{Sqlite, SqlitePool, SqliteQueryResult},
+    Error, FromRow, Transaction,
+};
+
+use crate::slug;
+
+// Two independent hierarchies, e.g. "XX век → Гражданская война" and
+// "Россия → Сибирь".
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Period,
+    Region,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 2] = [Kind::Period, Kind::Region];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Period => "period",
+            Kind::Region => "region",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Kind::Period => "Период",
+            Kind::Region => "География",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct Term {
+    pub id: u32,
+    pub kind: Kind,
+    pub parent_id: Option<u32>,
+    pub name: String,
+    pub slug: String,
+}
+
+// A term and how deep it sits in its tree
+pub struct Node {
+    pub term: Term,
+    pub depth: usize,
+}
+
+impl Node {
+    pub fn indent(&self) -> usize {
+        self.depth * 20
+    }
+}
+
+// What terms can be attached to; each has its own link table.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Entry {
+    Post,
+    Book,
+    Publication,
+}
+
+impl Entry {
+    pub(crate) fn table(&self) -> &'static str {
+        match self {
+            Entry::Post => "post_terms",
+            Entry::Book => "book_terms",
+            Entry::Publication => "publication_terms",
+        }
+    }
+
+    pub(crate) fn column(&self) -> &'static str {
+        match self {
+            Entry::Post => "post_id",
+            Entry::Book => "book_id",
+            Entry::Publication => "publication_id",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TermLink {
+    pub entry: Entry,
+    pub entry_id: u32,
+    pub term_id: u32,
+}
+
+// Selected terms by slug; an empty one doesn't filter.
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Facets {
+    pub period: String,
+    pub region: String,
+}
+
+impl Facets {
+    pub fn is_empty(&self) -> bool {
+        self.period.is_empty() && self.region.is_empty()
+    }
+
+    pub fn selected(&self, kind: Kind) -> &str {
+        match kind {
+            Kind::Period => &self.period,
+            Kind::Region => &self.region,
+        }
+    }
+
+    // Selecting the selected term again clears it
+    pub fn link(&self, kind: Kind, slug: &str) -> String {
+        let mut facets = self.clone();
+        let value = match kind {
+            Kind::Period => &mut facets.period,
+            Kind::Region => &mut facets.region,
+        };
+        *value = if value == slug {
+            String::new()
+        } else {
+            slug.to_string()
+        };
+        facets.query()
+    }
+
+    pub fn query(&self) -> String {
+        let pairs = [("period", &self.period), ("region", &self.region)]
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .collect::<Vec<_>>();
+        format!(
+            "?{}",
+            serde_urlencoded::to_string(pairs).unwrap_or_default()
+        )
+    }
+
+    // A condition on `alias` matching entries attached to the selected
+    // terms or any term below them; binds `period` and `region` twice.
+    pub fn condition(entry: Entry, alias: &str) -> String {
+        let matches = format!(
+            "(? = '' OR EXISTS (SELECT 1 FROM {table} WHERE {table}.{column} = {alias}.id \
+             AND {table}.term_id IN (WITH RECURSIVE subtree(id) AS \
+             (SELECT id FROM terms WHERE slug = ? \
+             UNION ALL SELECT terms.id FROM terms JOIN subtree ON terms.parent_id = subtree.id) \
+             SELECT id FROM subtree)))",
+            table = entry.table(),
+            column = entry.column(),
+            alias = alias,
+        );
+        format!("{} AND {}", matches, matches)
+    }
+
+    pub fn binds(&self) -> [&str; 4] {
+        [&self.period, &self.period, &self.region, &self.region]
+    }
+}
+
+// The facets of a public list and the terms to choose from
+pub struct Panel {
+    pub facets: Facets,
+    pub groups: Vec<(Kind, Vec<Node>)>,
+}
+
+impl Panel {
+    pub async fn load(db: &SqlitePool, facets: Facets) -> Result<Self, Error> {
+        let terms = Term::list(db).await?;
+        let groups = Kind::ALL
+            .into_iter()
+            .map(|kind| (kind, Term::tree(&terms, kind)))
+            .collect();
+        Ok(Panel { facets, groups })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.iter().all(|(_, nodes)| nodes.is_empty())
+    }
+
+    pub fn is_selected(&self, kind: Kind, slug: &str) -> bool {
+        self.facets.selected(kind) == slug
+    }
+}
+
+impl Term {
+    pub async fn list(db: &SqlitePool) -> Result<Vec<Self>, Error> {
+        query_as::<_, Term>("SELECT * FROM terms ORDER BY name")
+            .fetch_all(db)
+            .await
+    }
+
+    pub async fn fetch(db: &SqlitePool, id: u32) -> Result<Self, Error> {
+        query_as::<_, Term>("SELECT * FROM terms WHERE id = ?")
+            .bind(id)
+            .fetch_one(db)
+            .await
+    }
+
+    // Depth first, siblings by name
+    pub fn tree(terms: &[Term], kind: Kind) -> Vec<Node> {
+        fn visit(
+            terms: &[Term],
+            kind: Kind,
+            parent: Option<u32>,
+            depth: usize,
+            nodes: &mut Vec<Node>,
+        ) {
+            for term in terms
+                .iter()
+                .filter(|term| term.kind == kind && term.parent_id == parent)
+            {
+                nodes.push(Node {
+                    term: term.clone(),
+                    depth,
+                });
+                visit(terms, kind, Some(term.id), depth + 1, nodes);
+            }
+        }
+        let mut nodes = Vec::new();
+        visit(terms, kind, None, 0, &mut nodes);
+        nodes
+    }
+
+    // From the root down to the term itself
+    pub async fn ancestors(db: &SqlitePool, id: u32) -> Result<Vec<Self>, Error> {
+        let mut chain = query_as::<_, Term>(
+            "WITH RECURSIVE chain(id, parent_id, depth) AS \
+             (SELECT id, parent_id, 0 FROM terms WHERE id = ? \
+             UNION ALL SELECT terms.id, terms.parent_id, chain.depth + 1 FROM terms \
+             JOIN chain ON terms.id = chain.parent_id) \
+             SELECT terms.* FROM terms JOIN chain ON terms.id = chain.id ORDER BY chain.depth desc",
+        )
+        .bind(id)
+        .fetch_all(db)
+        .await?;
+        chain.dedup_by_key(|term| term.id);
+        Ok(chain)
+    }
+
+    pub async fn create(
+        db: &SqlitePool,
+        kind: Kind,
+        parent_id: Option<u32>,
+        name: &str,
+    ) -> Result<SqliteQueryResult, Error> {
+        let slugs: Vec<(String,)> = query_as("SELECT slug FROM terms").fetch_all(db).await?;
+        let mut base = slug::slugify(name);
+        if base.is_empty() {
+            base = kind.as_str().to_string();
+        }
+        let mut slug = base.clone();
+        let mut number = 2;
+        while slugs.iter().any(|(taken,)| *taken == slug) {
+            slug = format!("{}-{}", base, number);
+            number += 1;
+        }
+        query("INSERT into terms (kind, parent_id, name, slug) values (?, ?, ?, ?)")
+            .bind(kind)
+            .bind(parent_id)
+            .bind(name)
+            .bind(slug)
+            .execute(db)
+            .await
+    }
+
+    // The slug stays, so links to the term keep working
+    pub async fn rename(db: &SqlitePool, id: u32, name: &str) -> Result<SqliteQueryResult, Error> {
+        query("UPDATE terms SET name = ? WHERE id = ?")
+            .bind(name)
+            .bind(id)
+            .execute(db)
+            .await
+    }
+
+    // Terms below it and their links go too
+    pub async fn delete(db: &SqlitePool, id: u32) -> Result<SqliteQueryResult, Error> {
+        query("DELETE FROM terms WHERE id = ?")
+            .bind(id)
+            .execute(db)
+            .await
+    }
+
+    // Matched by slug like posts; the parent must already be given the id
+    // it has here.
+    pub async fn upsert(tx: &mut Transaction<'_, Sqlite>, term: &Term) -> Result<u32, Error> {
+        query_scalar(
+            "INSERT into terms (kind, parent_id, name, slug) values (?, ?, ?, ?) \
+             ON CONFLICT(slug) DO UPDATE SET kind = excluded.kind, parent_id = excluded.parent_id, \
+             name = excluded.name RETURNING id",
+        )
+        .bind(term.kind)
+        .bind(term.parent_id)
+        .bind(&term.name)
+        .bind(&term.slug)
+        .fetch_one(&mut *tx)
+        .await
+    }
+
+    pub async fn for_entry(db: &SqlitePool, entry: Entry, id: u32) -> Result<Vec<Self>, Error> {
+        query_as::<_, Term>(&format!(
+            "SELECT terms.* FROM terms JOIN {table} ON {table}.term_id = terms.id \
+             WHERE {table}.{column} = ? ORDER BY terms.kind, terms.name",
+            table = entry.table(),
+            column = entry.column(),
+        ))
+        .bind(id)
+        .fetch_all(db)
+        .await
+    }
+
+    pub async fn links(db: &SqlitePool) -> Result<Vec<TermLink>, Error> {
+        let mut links = Vec::new();
+        for entry in [Entry::Post, Entry::Book, Entry::Publication] {
+            let rows: Vec<(u32, u32)> = query_as(&format!(
+                "SELECT {}, term_id FROM {}",
+                entry.column(),
+                entry.table()
+            ))
+            .fetch_all(db)
+            .await?;
+            links.extend(rows.into_iter().map(|(entry_id, term_id)| TermLink {
+                entry,
+                entry_id,
+                term_id,
+            }));
+        }
+        Ok(links)
+    }
+
+    pub async fn link_in(
+        tx: &mut Transaction<'_, Sqlite>,
+        link: &TermLink,
+    ) -> Result<SqliteQueryResult, Error> {
+        query(&format!(
+            "INSERT OR IGNORE into {} ({}, term_id) values (?, ?)",
+            link.entry.table(),
+            link.entry.column()
+        ))
+        .bind(link.entry_id)
+        .bind(link.term_id)
+        .execute(&mut *tx)
+        .await
+    }
+
+    pub async fn replace(
+        db: &SqlitePool,
+        entry: Entry,
+        id: u32,
+        term_ids: &[u32],
+    ) -> Result<(), Error> {
+        let mut tx = db.begin().await?;
+        Self::replace_in(&mut tx, entry, id, term_ids).await?;
+        tx.commit().await
+    }
+
+    pub async fn replace_in(
+        tx: &mut Transaction<'_, Sqlite>,
+        entry: Entry,
+        id: u32,
+        term_ids: &[u32],
+    ) -> Result<(), Error> {
+        query(&format!(
+            "DELETE FROM {} WHERE {} = ?",
+            entry.table(),
+            entry.column()
+        ))
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        for term_id in term_ids {
+            let link = TermLink {
+                entry,
+                entry_id: id,
+                term_id: *term_id,
+            };
+            Self::link_in(tx, &link).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_encodes_values() {
+        let facets = Facets {
+            period: "xx vek".to_string(),
+            region: "a&b=c".to_string(),
+        };
+        assert_eq!(facets.query(), "?period=xx+vek&region=a%26b%3Dc");
+        assert_eq!(facets.link(Kind::Region, "a&b=c"), "?period=xx+vek");
+        assert_eq!(Facets::default().query(), "?");
+    }
+}
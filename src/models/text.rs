@@ -1,32 +1,62 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{
-    query, query_as,
-    sqlite::{SqlitePool, SqliteQueryResult},
-    Error, FromRow,
+    query, query_as, query_scalar,
+    sqlite::{Sqlite, SqlitePool, SqliteQueryResult},
+    Error, FromRow, Transaction,
 };
+use utoipa::ToSchema;
 
-#[derive(Deserialize, Debug)]
+use crate::models::{self, Page, Pagination};
+
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct NewText {
     pub name: String,
     pub link: String,
     pub description: String,
 }
 
-#[derive(FromRow, Clone)]
+#[derive(Serialize, Deserialize, FromRow, Clone, ToSchema)]
 pub struct Text {
     pub id: u32,
     pub name: String,
     pub link: String,
     pub description: String,
+    // Where the entry goes on the home page; exports made before it
+    // existed keep the order of ids
+    #[serde(default)]
+    pub position: i64,
+    // Drafts are only seen in the admin
+    #[serde(default)]
+    pub draft: bool,
+    // Followed links from the site, see `Section::download`
+    #[serde(default)]
+    pub downloads: i64,
 }
 
 impl Text {
     pub async fn list(db: &SqlitePool) -> Result<Vec<Self>, Error> {
-        query_as::<_, Text>("SELECT * FROM texts ORDER BY id")
+        query_as::<_, Text>("SELECT * FROM texts ORDER BY position, id")
+            .fetch_all(db)
+            .await
+    }
+
+    pub async fn published(db: &SqlitePool) -> Result<Vec<Self>, Error> {
+        query_as::<_, Text>("SELECT * FROM texts WHERE draft = 0 ORDER BY position, id")
             .fetch_all(db)
             .await
     }
 
+    pub async fn page(db: &SqlitePool, pagination: Pagination) -> Result<Page<Self>, Error> {
+        let items =
+            query_as::<_, Text>("SELECT * FROM texts ORDER BY position, id LIMIT ? OFFSET ?")
+                .bind(pagination.limit())
+                .bind(pagination.offset())
+                .fetch_all(db)
+                .await?;
+        let (total,): (i64,) = query_as("SELECT count(*) FROM texts").fetch_one(db).await?;
+        Ok(Page::new(items, pagination, total))
+    }
+
     pub async fn fetch(db: &SqlitePool, id: u32) -> Result<Self, Error> {
         query_as::<_, Text>("SELECT * FROM texts WHERE id = ?")
             .bind(id)
@@ -35,12 +65,15 @@ impl Text {
     }
 
     pub async fn create(db: &SqlitePool, new_text: NewText) -> Result<SqliteQueryResult, Error> {
-        query("INSERT into texts (name, link, description) values (?, ?, ?)")
-            .bind(new_text.name)
-            .bind(new_text.link)
-            .bind(new_text.description)
-            .execute(db)
-            .await
+        query(
+            "INSERT into texts (name, link, description, position) \
+             values (?, ?, ?, (SELECT coalesce(max(position), 0) + 1 FROM texts))",
+        )
+        .bind(new_text.name)
+        .bind(new_text.link)
+        .bind(new_text.description)
+        .execute(db)
+        .await
     }
 
     pub async fn update(
@@ -57,6 +90,39 @@ impl Text {
             .await
     }
 
+    // Matched by link, since ids differ between sites; returns the id the
+    // entry has here, which is a new one unless the link is already listed.
+    pub async fn upsert(tx: &mut Transaction<'_, Sqlite>, text: &Text) -> Result<u32, Error> {
+        let id = models::id_by_link(tx, "texts", &text.link).await?;
+        query_scalar(
+            "INSERT into texts (id, name, link, description, position, draft, downloads) \
+             values (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, link = excluded.link, \
+             description = excluded.description, position = excluded.position, \
+             draft = excluded.draft, downloads = excluded.downloads RETURNING id",
+        )
+        .bind(id)
+        .bind(&text.name)
+        .bind(&text.link)
+        .bind(&text.description)
+        .bind(match text.position {
+            0 => text.id as i64,
+            position => position,
+        })
+        .bind(text.draft)
+        .bind(text.downloads)
+        .fetch_one(&mut *tx)
+        .await
+    }
+
+    pub async fn ids(db: &SqlitePool) -> Result<Vec<u32>, Error> {
+        models::ordered_ids(db, "texts").await
+    }
+
+    pub async fn reorder(db: &SqlitePool, ids: &[u32]) -> Result<bool, Error> {
+        models::reorder(db, "texts", ids).await
+    }
+
     pub async fn delete(db: &SqlitePool, id: u32) -> Result<SqliteQueryResult, Error> {
         query("DELETE FROM texts WHERE id = ?")
             .bind(id)
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use sqlx::{
+    query, query_as,
+    sqlite::{SqlitePool, SqliteQueryResult},
+    Error, FromRow,
+};
+
+use crate::auth::Scope;
+
+// Only a hash of the secret is stored, the secret itself is shown once
+// when the token is created.
+#[derive(FromRow, Clone)]
+pub struct Token {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub hash: String,
+    // Comma-separated scope names
+    pub scopes: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Token {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.split(',').any(|name| name == scope.as_str())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    pub fn scope_labels(&self) -> String {
+        Scope::ALL
+            .iter()
+            .filter(|scope| self.allows(**scope))
+            .map(|scope| scope.label())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub async fn list(db: &SqlitePool, user_id: i64) -> Result<Vec<Self>, Error> {
+        query_as::<_, Token>("SELECT * FROM tokens WHERE user_id = ? ORDER BY created_at desc")
+            .bind(user_id)
+            .fetch_all(db)
+            .await
+    }
+
+    pub async fn fetch_by_hash(db: &SqlitePool, hash: &str) -> Result<Self, Error> {
+        query_as::<_, Token>("SELECT * FROM tokens WHERE hash = ?")
+            .bind(hash)
+            .fetch_one(db)
+            .await
+    }
+
+    pub async fn create(
+        db: &SqlitePool,
+        user_id: i64,
+        name: &str,
+        hash: &str,
+        scopes: &[Scope],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<SqliteQueryResult, Error> {
+        let scopes = scopes
+            .iter()
+            .map(|scope| scope.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        query("INSERT into tokens (user_id, name, hash, scopes, expires_at) values (?, ?, ?, ?, ?)")
+            .bind(user_id)
+            .bind(name)
+            .bind(hash)
+            .bind(scopes)
+            .bind(expires_at)
+            .execute(db)
+            .await
+    }
+
+    pub async fn touch(db: &SqlitePool, id: i64) -> Result<SqliteQueryResult, Error> {
+        query("UPDATE tokens SET last_used_at = ? WHERE id = ?")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(db)
+            .await
+    }
+
+    // Scoped to the owner, so one user can't revoke another's tokens
+    pub async fn delete(
+        db: &SqlitePool,
+        id: i64,
+        user_id: i64,
+    ) -> Result<SqliteQueryResult, Error> {
+        query("DELETE FROM tokens WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(db)
+            .await
+    }
+}
@@ -0,0 +1,78 @@
+use sqlx::{
+    query, query_as,
+    sqlite::{SqlitePool, SqliteQueryResult, SqliteRow},
+    Error, FromRow, Row,
+};
+
+use crate::auth::Role;
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub id: i64,
+    pub name: String,
+    pub password_hash: String,
+    pub role: Role,
+}
+
+// Every account is an administrator, there are no other roles yet.
+impl FromRow<'_, SqliteRow> for User {
+    fn from_row(row: &SqliteRow) -> Result<Self, Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            password_hash: row.try_get("password_hash")?,
+            role: Role::Admin,
+        })
+    }
+}
+
+impl User {
+    pub async fn list(db: &SqlitePool) -> Result<Vec<Self>, Error> {
+        query_as::<_, User>("SELECT * FROM users ORDER BY name")
+            .fetch_all(db)
+            .await
+    }
+
+    pub async fn fetch_by_name(db: &SqlitePool, name: &str) -> Result<Self, Error> {
+        query_as::<_, User>("SELECT * FROM users WHERE name = ?")
+            .bind(name)
+            .fetch_one(db)
+            .await
+    }
+
+    pub async fn count(db: &SqlitePool) -> Result<i64, Error> {
+        let (count,): (i64,) = query_as("SELECT count(*) FROM users").fetch_one(db).await?;
+        Ok(count)
+    }
+
+    pub async fn create(
+        db: &SqlitePool,
+        name: &str,
+        password_hash: &str,
+    ) -> Result<SqliteQueryResult, Error> {
+        query("INSERT into users (name, password_hash) values (?, ?)")
+            .bind(name)
+            .bind(password_hash)
+            .execute(db)
+            .await
+    }
+
+    pub async fn set_password(
+        db: &SqlitePool,
+        name: &str,
+        password_hash: &str,
+    ) -> Result<SqliteQueryResult, Error> {
+        query("UPDATE users SET password_hash = ? WHERE name = ?")
+            .bind(password_hash)
+            .bind(name)
+            .execute(db)
+            .await
+    }
+
+    pub async fn delete(db: &SqlitePool, name: &str) -> Result<SqliteQueryResult, Error> {
+        query("DELETE FROM users WHERE name = ?")
+            .bind(name)
+            .execute(db)
+            .await
+    }
+}
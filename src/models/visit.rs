@@ -0,0 +1,118 @@
+use chrono::NaiveDate;
+use sqlx::{query, query_as, sqlite::SqlitePool, Error, FromRow};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Page,
+    Download,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Page => "page",
+            Kind::Download => "download",
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub struct Day {
+    pub day: NaiveDate,
+    pub views: i64,
+    pub visitors: i64,
+}
+
+// Visitors are summed over days, so someone who came back on another day
+// is counted again
+#[derive(FromRow)]
+pub struct Total {
+    pub path: String,
+    pub views: i64,
+    pub visitors: i64,
+}
+
+pub async fn record(
+    db: &SqlitePool,
+    day: NaiveDate,
+    kind: Kind,
+    path: &str,
+    hash: &str,
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+    let new = query("INSERT OR IGNORE INTO visitors (day, kind, path, hash) VALUES (?, ?, ?, ?)")
+        .bind(day)
+        .bind(kind.as_str())
+        .bind(path)
+        .bind(hash)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    query(
+        "INSERT INTO visits (day, kind, path, views, visitors) VALUES (?, ?, ?, 1, ?) \
+         ON CONFLICT (day, kind, path) DO UPDATE SET \
+         views = views + 1, visitors = visitors + excluded.visitors",
+    )
+    .bind(day)
+    .bind(kind.as_str())
+    .bind(path)
+    .bind(new as i64)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+// The hashes of a day that is over can't be told apart from new ones
+// anyway, since the salt has changed
+pub async fn forget_before(db: &SqlitePool, day: NaiveDate) -> Result<(), Error> {
+    query("DELETE FROM visitors WHERE day < ?")
+        .bind(day)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Every day since the given one, including those without visits
+pub async fn daily(db: &SqlitePool, kind: Kind, since: NaiveDate) -> Result<Vec<Day>, Error> {
+    let recorded: Vec<Day> = query_as(
+        "SELECT day, sum(views) AS views, sum(visitors) AS visitors FROM visits \
+         WHERE kind = ? AND day >= ? GROUP BY day ORDER BY day",
+    )
+    .bind(kind.as_str())
+    .bind(since)
+    .fetch_all(db)
+    .await?;
+    let today = chrono::Utc::now().date_naive();
+    let mut recorded = recorded.into_iter().peekable();
+    Ok(since
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .map(
+            |day| match recorded.next_if(|recorded| recorded.day == day) {
+                Some(recorded) => recorded,
+                None => Day {
+                    day,
+                    views: 0,
+                    visitors: 0,
+                },
+            },
+        )
+        .collect())
+}
+
+pub async fn top(
+    db: &SqlitePool,
+    kind: Kind,
+    since: NaiveDate,
+    limit: u32,
+) -> Result<Vec<Total>, Error> {
+    query_as(
+        "SELECT path, sum(views) AS views, sum(visitors) AS visitors FROM visits \
+         WHERE kind = ? AND day >= ? GROUP BY path ORDER BY views DESC, path LIMIT ?",
+    )
+    .bind(kind.as_str())
+    .bind(since)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
@@ -0,0 +1,194 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{remove_file, rename, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+const MB: usize = 1024 * 1024;
+
+pub struct Kind {
+    pub mime: &'static str,
+    pub extension: &'static str,
+    pub max_size: usize,
+    magic: &'static [&'static [u8]],
+}
+
+pub const ALLOWED: [Kind; 6] = [
+    Kind {
+        mime: "image/jpeg",
+        extension: "jpg",
+        max_size: 8 * MB,
+        magic: &[b"\xFF\xD8\xFF"],
+    },
+    Kind {
+        mime: "image/png",
+        extension: "png",
+        max_size: 8 * MB,
+        magic: &[b"\x89PNG\r\n\x1A\n"],
+    },
+    Kind {
+        mime: "image/webp",
+        extension: "webp",
+        max_size: 8 * MB,
+        magic: &[b"RIFF"],
+    },
+    Kind {
+        mime: "image/gif",
+        extension: "gif",
+        max_size: 8 * MB,
+        magic: &[b"GIF87a", b"GIF89a"],
+    },
+    Kind {
+        mime: "application/pdf",
+        extension: "pdf",
+        max_size: 32 * MB,
+        magic: &[b"%PDF-"],
+    },
+    Kind {
+        mime: "image/vnd.djvu",
+        extension: "djvu",
+        max_size: 32 * MB,
+        magic: &[b"AT&TFORM"],
+    },
+];
+
+#[derive(Debug)]
+pub enum UploadError {
+    Empty,
+    Unsupported,
+    TooLarge { mime: &'static str, max_size: usize },
+    Io,
+}
+
+impl UploadError {
+    pub fn message(&self) -> String {
+        match self {
+            UploadError::Empty => "Файл не выбран или пуст".to_string(),
+            UploadError::Unsupported => format!(
+                "Неподдерживаемый тип файла, допустимы: {}",
+                ALLOWED
+                    .iter()
+                    .map(|kind| kind.extension)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            UploadError::TooLarge { mime, max_size } => {
+                format!("Файл {} больше {} Мб", mime, max_size / MB)
+            }
+            UploadError::Io => "Не удалось сохранить файл".to_string(),
+        }
+    }
+}
+
+impl From<std::io::Error> for UploadError {
+    fn from(_: std::io::Error) -> Self {
+        UploadError::Io
+    }
+}
+
+// The type is decided by the leading bytes of the file, never by the name or
+// the content type the browser sent.
+pub fn sniff(bytes: &[u8]) -> Option<&'static Kind> {
+    ALLOWED.iter().find(|kind| {
+        kind.magic.iter().any(|magic| bytes.starts_with(magic))
+            && (kind.extension != "webp" || bytes.get(8..12) == Some(b"WEBP"))
+    })
+}
+
+impl Kind {
+    pub fn is_image(&self) -> bool {
+        matches!(self.extension, "jpg" | "png" | "webp")
+    }
+}
+
+pub struct Accepted {
+    pub kind: &'static Kind,
+    pub hash: String,
+}
+
+impl Accepted {
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", self.hash, self.kind.extension)
+    }
+}
+
+#[derive(Clone)]
+pub struct Storage {
+    root: PathBuf,
+}
+
+impl Storage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn accept(&self, bytes: &[u8]) -> Result<Accepted, UploadError> {
+        if bytes.is_empty() {
+            return Err(UploadError::Empty);
+        }
+        let kind = sniff(bytes).ok_or(UploadError::Unsupported)?;
+        if bytes.len() > kind.max_size {
+            return Err(UploadError::TooLarge {
+                mime: kind.mime,
+                max_size: kind.max_size,
+            });
+        }
+        Ok(Accepted {
+            kind,
+            hash: format!("{:x}", Sha256::digest(bytes)),
+        })
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).is_some_and(|path| path.exists())
+    }
+
+    // Identical uploads share one file: the name is the content hash, so an
+    // existing file already holds exactly these bytes.
+    pub fn store(&self, bytes: &[u8]) -> Result<String, UploadError> {
+        let accepted = self.accept(bytes)?;
+        let file_name = accepted.file_name();
+        let path = self.path(&file_name).ok_or(UploadError::Io)?;
+        if !path.exists() {
+            let temporary = path.with_extension("part");
+            File::create(&temporary)?.write_all(bytes)?;
+            rename(&temporary, &path)?;
+        }
+        Ok(file_name)
+    }
+
+    // For files that already have their final name, such as the ones
+    // restored from an export; an existing file is left as it is.
+    pub fn put(&self, name: &str, bytes: &[u8]) -> Result<(), UploadError> {
+        let path = self.path(name).ok_or(UploadError::Io)?;
+        if !path.exists() {
+            let temporary = path.with_extension("part");
+            File::create(&temporary)?.write_all(bytes)?;
+            rename(&temporary, &path)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), UploadError> {
+        let Some(path) = self.path(name) else {
+            return Ok(());
+        };
+        match remove_file(path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn path(&self, name: &str) -> Option<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            None
+        } else {
+            Some(self.root.join(name))
+        }
+    }
+}
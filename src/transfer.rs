@@ -0,0 +1,527 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqlitePool;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Seek, Write},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    images,
+    models::{
+        media::Media,
+        source::{NewSource, Source},
+        tag::Tag,
+        term::{Entry, Kind, Term, TermLink},
+    },
+    storage::Storage,
+    views::check_link,
+    Book, HistoryError, Post, Publication, Text,
+};
+
+// Bumped whenever the layout changes in a way older importers can't read.
+pub const VERSION: u32 = 1;
+
+// An archive holds the export under this name and the uploaded files it
+// refers to under `uploads/`.
+const CONTENT: &str = "content.json";
+const UPLOADS: &str = "uploads/";
+// Well above the size of any upload the site takes
+const MAX_ENTRY: u64 = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedPost {
+    #[serde(flatten)]
+    pub post: Post,
+    pub sources: Vec<NewSource>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub books: Vec<Book>,
+    pub publications: Vec<Publication>,
+    pub texts: Vec<Text>,
+    pub posts: Vec<ExportedPost>,
+    pub media: Vec<Media>,
+    #[serde(default)]
+    pub terms: Vec<Term>,
+    #[serde(default)]
+    pub term_links: Vec<TermLink>,
+}
+
+#[derive(Default)]
+pub struct Imported {
+    pub books: usize,
+    pub publications: usize,
+    pub texts: usize,
+    pub posts: usize,
+    pub media: usize,
+}
+
+pub async fn export(db: &SqlitePool) -> Result<Export, HistoryError> {
+    let mut posts = Vec::new();
+    for post in Post::list(db).await? {
+        let sources = Source::list(db, post.id).await?;
+        let tags = Tag::for_post(db, post.id).await?;
+        posts.push(ExportedPost {
+            post,
+            sources: sources.into_iter().map(NewSource::from).collect(),
+            tags: tags.into_iter().map(|tag| tag.name).collect(),
+        });
+    }
+    Ok(Export {
+        version: VERSION,
+        exported_at: Utc::now(),
+        books: Book::list(db).await?,
+        publications: Publication::list(db).await?,
+        texts: Text::list(db).await?,
+        posts,
+        media: Media::list(db, "").await?,
+        terms: Term::list(db).await?,
+        term_links: Term::links(db).await?,
+    })
+}
+
+// Entries are upserted, so importing the same export twice changes nothing;
+// entries missing from the export are left alone. Posts and terms are
+// matched by slug, catalog entries by link.
+pub async fn import(db: &SqlitePool, mut export: Export) -> Result<Imported, HistoryError> {
+    if export.version != VERSION {
+        return Err(HistoryError::Invalid(format!(
+            "Версия выгрузки {} не поддерживается, ожидается {}",
+            export.version, VERSION
+        )));
+    }
+    let links = export.books.iter_mut().map(|book| &mut book.link);
+    let links = links.chain(export.publications.iter_mut().map(|entry| &mut entry.link));
+    for link in links.chain(export.texts.iter_mut().map(|text| &mut text.link)) {
+        *link = check_link(std::mem::take(link))?;
+    }
+    let mut imported = Imported::default();
+    let mut tx = db.begin().await?;
+    // Everything is matched by a key of its own rather than by id, so links
+    // between entries get the ids their targets have here
+    let mut term_ids = HashMap::new();
+    // Parents go in before the terms below them
+    for kind in Kind::ALL {
+        for node in Term::tree(&export.terms, kind) {
+            let mut term = node.term;
+            term.parent_id = term.parent_id.and_then(|id| term_ids.get(&id).copied());
+            term_ids.insert(term.id, Term::upsert(&mut tx, &term).await?);
+        }
+    }
+    let mut book_ids = HashMap::new();
+    for book in export.books.iter() {
+        book_ids.insert(book.id, Book::upsert(&mut tx, book).await?);
+        imported.books += 1;
+    }
+    let mut publication_ids = HashMap::new();
+    for publication in export.publications.iter() {
+        let id = Publication::upsert(&mut tx, publication).await?;
+        publication_ids.insert(publication.id, id);
+        imported.publications += 1;
+    }
+    for text in export.texts.iter() {
+        Text::upsert(&mut tx, text).await?;
+        imported.texts += 1;
+    }
+    let mut post_ids = HashMap::new();
+    for exported in export.posts {
+        let id = Post::upsert(&mut tx, &exported.post).await?;
+        post_ids.insert(exported.post.id, id);
+        Source::replace_in(&mut tx, id, exported.sources).await?;
+        Tag::replace_in(&mut tx, id, exported.tags).await?;
+        imported.posts += 1;
+    }
+    for mut link in export.term_links {
+        let entry_ids = match link.entry {
+            Entry::Post => &post_ids,
+            Entry::Book => &book_ids,
+            Entry::Publication => &publication_ids,
+        };
+        let (Some(entry_id), Some(term_id)) =
+            (entry_ids.get(&link.entry_id), term_ids.get(&link.term_id))
+        else {
+            continue;
+        };
+        link.entry_id = *entry_id;
+        link.term_id = *term_id;
+        Term::link_in(&mut tx, &link).await?;
+    }
+    for media in export.media.iter() {
+        Media::upsert(&mut tx, media).await?;
+        imported.media += 1;
+    }
+    tx.commit().await?;
+    Ok(imported)
+}
+
+impl Export {
+    // Covers and library files with their image variants.
+    pub fn files(&self) -> Vec<String> {
+        let mut files = Vec::new();
+        for exported in self.posts.iter() {
+            if !exported.post.cover.is_empty() {
+                files.extend(images::with_variants(&exported.post.cover));
+            }
+        }
+        for media in self.media.iter() {
+            if media.has_variants() {
+                files.extend(images::with_variants(&media.file_name));
+            } else {
+                files.push(media.file_name.clone());
+            }
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+// Files that are gone from the disk are left out, `check-uploads` is where
+// they get reported.
+pub fn write_archive<W: Write + Seek>(
+    writer: W,
+    export: &Export,
+    storage: &Storage,
+) -> Result<(), HistoryError> {
+    let mut zip = ZipWriter::new(writer);
+    let json = serde_json::to_vec_pretty(export).map_err(|_| HistoryError::InternalError)?;
+    zip.start_file(
+        CONTENT,
+        SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+    )
+    .map_err(|_| HistoryError::InternalError)?;
+    zip.write_all(&json)?;
+    // Images are compressed already
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for file in export.files() {
+        let Some(path) = storage.path(&file).filter(|path| path.exists()) else {
+            continue;
+        };
+        let bytes = std::fs::read(path)?;
+        zip.start_file(format!("{}{}", UPLOADS, file), stored)
+            .map_err(|_| HistoryError::InternalError)?;
+        zip.write_all(&bytes)?;
+    }
+    zip.finish().map_err(|_| HistoryError::InternalError)?;
+    Ok(())
+}
+
+// The uploaded files of an archive, written out only once its export has
+// been imported.
+pub struct Uploads<R> {
+    zip: ZipArchive<R>,
+    // Checked entries, the only ones that get written
+    files: HashSet<String>,
+}
+
+fn too_large() -> HistoryError {
+    HistoryError::Invalid(format!(
+        "Файлы в выгрузке не могут быть больше {} Мб",
+        MAX_ENTRY / 1024 / 1024
+    ))
+}
+
+// Reads at most MAX_ENTRY bytes, whatever size the entry claims to have
+fn read_entry(entry: impl Read) -> Result<Vec<u8>, HistoryError> {
+    let mut bytes = Vec::new();
+    entry.take(MAX_ENTRY + 1).read_to_end(&mut bytes)?;
+    match bytes.len() as u64 > MAX_ENTRY {
+        true => Err(too_large()),
+        false => Ok(bytes),
+    }
+}
+
+// A file must pass for an upload of the type its name says. Other files
+// are named by the hash of their content; images are stored re-encoded
+// under the hash of what was sent, so they have to decode instead.
+fn check_upload(storage: &Storage, name: &str, bytes: &[u8]) -> Result<(), HistoryError> {
+    let accepted = storage
+        .accept(bytes)
+        .map_err(|error| HistoryError::Invalid(format!("{}: {}", name, error.message())))?;
+    let valid = match accepted.kind.is_image() {
+        true => images::decode(bytes).is_ok(),
+        false => name == accepted.file_name(),
+    };
+    if !valid || !name.ends_with(&format!(".{}", accepted.kind.extension)) {
+        return Err(HistoryError::Invalid(format!(
+            "Файл {} в выгрузке не совпадает со своим именем",
+            name
+        )));
+    }
+    Ok(())
+}
+
+// Checks the whole archive without writing anything, so a broken one leaves
+// the uploads as they were. Files the export doesn't refer to are skipped.
+pub fn read_archive<R: Read + Seek>(
+    reader: R,
+    storage: &Storage,
+) -> Result<(Export, Uploads<R>), HistoryError> {
+    let invalid = || HistoryError::Invalid("Файл не похож на выгрузку сайта".into());
+    let mut zip = ZipArchive::new(reader).map_err(|_| invalid())?;
+    let export: Export = {
+        let content = read_entry(zip.by_name(CONTENT).map_err(|_| invalid())?)?;
+        serde_json::from_slice(&content)
+            .map_err(|error| HistoryError::Invalid(error.to_string()))?
+    };
+    let expected: HashSet<String> = export.files().into_iter().collect();
+    let mut files = HashSet::new();
+    for index in 0..zip.len() {
+        let file = zip.by_index(index).map_err(|_| invalid())?;
+        let Some(name) = file.name().strip_prefix(UPLOADS).map(str::to_string) else {
+            continue;
+        };
+        // Storage refuses names that would leave the uploads directory
+        if !file.is_file() || !expected.contains(&name) || storage.path(&name).is_none() {
+            continue;
+        }
+        if file.size() > MAX_ENTRY {
+            return Err(too_large());
+        }
+        check_upload(storage, &name, &read_entry(file)?)?;
+        files.insert(name);
+    }
+    Ok((export, Uploads { zip, files }))
+}
+
+impl<R: Read + Seek> Uploads<R> {
+    // Files that already exist are kept since names are content hashes.
+    pub fn restore(mut self, storage: &Storage) -> Result<(), HistoryError> {
+        for index in 0..self.zip.len() {
+            let file = self
+                .zip
+                .by_index(index)
+                .map_err(|_| HistoryError::InternalError)?;
+            let Some(name) = file.name().strip_prefix(UPLOADS).map(str::to_string) else {
+                continue;
+            };
+            if !self.files.contains(&name) {
+                continue;
+            }
+            storage.put(&name, &read_entry(file)?)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn is_archive(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"PK\x03\x04")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models;
+    use sqlx::query;
+    use std::{fs, io::Cursor};
+    use tempfile::TempDir;
+
+    async fn environment(dir: &TempDir, name: &str) -> (SqlitePool, Storage) {
+        let db = models::connect(&dir.path().join(format!("{}.db", name)))
+            .await
+            .unwrap();
+        let storage = Storage::new(dir.path().join(name));
+        fs::create_dir_all(storage.root()).unwrap();
+        (db, storage)
+    }
+
+    async fn add_post(db: &SqlitePool, id: u32, title: &str, slug: &str, cover: &str) {
+        query("INSERT INTO posts (id, title, lead, body, cover, slug) VALUES (?, ?, '', '', ?, ?)")
+            .bind(id)
+            .bind(title)
+            .bind(cover)
+            .bind(slug)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn titles(db: &SqlitePool) -> Vec<(String, String)> {
+        sqlx::query_as("SELECT slug, title FROM posts ORDER BY slug")
+            .fetch_all(db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn import_matches_posts_by_slug() {
+        let dir = TempDir::new().unwrap();
+        let (source, _) = environment(&dir, "source").await;
+        add_post(&source, 1, "Первый", "first", "").await;
+        add_post(&source, 2, "Второй", "second", "").await;
+        let (target, _) = environment(&dir, "target").await;
+        add_post(&target, 1, "Старый второй", "second", "").await;
+        add_post(&target, 2, "Свой", "own", "").await;
+        let export = export(&source).await.ok().unwrap();
+        assert!(import(&target, export).await.is_ok());
+        assert_eq!(
+            titles(&target).await,
+            vec![
+                ("first".to_string(), "Первый".to_string()),
+                ("own".to_string(), "Свой".to_string()),
+                ("second".to_string(), "Второй".to_string()),
+            ]
+        );
+        assert_eq!(Post::fetch(&target, 1).await.unwrap().slug, "second");
+    }
+
+    async fn add_book(db: &SqlitePool, id: u32, name: &str, link: &str) {
+        query("INSERT INTO books (id, name, link, description) VALUES (?, ?, ?, '')")
+            .bind(id)
+            .bind(name)
+            .bind(link)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn add_term(db: &SqlitePool, id: u32, parent_id: Option<u32>, slug: &str) {
+        query("INSERT INTO terms (id, kind, parent_id, name, slug) VALUES (?, 'region', ?, ?, ?)")
+            .bind(id)
+            .bind(parent_id)
+            .bind(slug)
+            .bind(slug)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    fn cover(storage: &Storage) -> String {
+        let mut bytes = Vec::new();
+        image::RgbImage::from_pixel(64, 48, image::Rgb([10, 100, 50]))
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        let hash = storage.accept(&bytes).unwrap().hash;
+        images::process(storage, &bytes, &hash).ok().unwrap()
+    }
+
+    async fn add_media(db: &SqlitePool, storage: &Storage, name: &str, bytes: &[u8]) {
+        storage.put(name, bytes).unwrap();
+        query("INSERT INTO media (file_name, original_name, mime, size) VALUES (?, 'a.pdf', 'application/pdf', ?)")
+            .bind(name)
+            .bind(bytes.len() as i64)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn archive(db: &SqlitePool, storage: &Storage) -> Cursor<Vec<u8>> {
+        let export = export(db).await.ok().unwrap();
+        let mut archive = Cursor::new(Vec::new());
+        assert!(write_archive(&mut archive, &export, storage).is_ok());
+        archive.set_position(0);
+        archive
+    }
+
+    #[tokio::test]
+    async fn import_matches_catalog_by_link_and_terms_by_slug() {
+        let dir = TempDir::new().unwrap();
+        let (source, _) = environment(&dir, "source").await;
+        add_book(&source, 1, "Новая", "https://example.com/new.pdf").await;
+        add_term(&source, 1, None, "region").await;
+        add_term(&source, 2, Some(1), "town").await;
+        query("INSERT INTO book_terms (book_id, term_id) VALUES (1, 2)")
+            .execute(&source)
+            .await
+            .unwrap();
+        let (target, _) = environment(&dir, "target").await;
+        add_book(&target, 1, "Своя", "https://example.com/own.pdf").await;
+        add_term(&target, 1, None, "own").await;
+        add_term(&target, 2, None, "other").await;
+        for _ in 0..2 {
+            let export = export(&source).await.ok().unwrap();
+            assert!(import(&target, export).await.is_ok());
+        }
+        let books: Vec<(u32, String)> = sqlx::query_as("SELECT id, name FROM books ORDER BY id")
+            .fetch_all(&target)
+            .await
+            .unwrap();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[0], (1, "Своя".to_string()));
+        let terms = Term::list(&target).await.unwrap();
+        let slugs = |id| {
+            terms
+                .iter()
+                .find(|term| term.id == id)
+                .unwrap()
+                .slug
+                .clone()
+        };
+        assert_eq!(
+            (slugs(1), slugs(2)),
+            ("own".to_string(), "other".to_string())
+        );
+        let town = terms.iter().find(|term| term.slug == "town").unwrap();
+        let region = terms.iter().find(|term| term.slug == "region").unwrap();
+        assert_eq!(town.parent_id, Some(region.id));
+        let linked = Term::for_entry(&target, Entry::Book, books[1].0)
+            .await
+            .unwrap();
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].slug, "town");
+        assert!(Term::for_entry(&target, Entry::Book, 1)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn archive_restores_checked_uploads() {
+        let dir = TempDir::new().unwrap();
+        let (source, uploads) = environment(&dir, "source").await;
+        let cover = cover(&uploads);
+        add_post(&source, 1, "Первый", "first", &cover).await;
+        let pdf = b"%PDF-1.4 document";
+        let name = uploads.accept(pdf).unwrap().file_name();
+        add_media(&source, &uploads, &name, pdf).await;
+        let (target, storage) = environment(&dir, "target").await;
+        let (export, files) = read_archive(archive(&source, &uploads).await, &storage)
+            .ok()
+            .unwrap();
+        assert!(import(&target, export).await.is_ok());
+        assert!(files.restore(&storage).is_ok());
+        for file in images::with_variants(&cover).iter().chain([&name]) {
+            assert!(storage.exists(file), "{} is missing", file);
+        }
+    }
+
+    #[tokio::test]
+    async fn archive_with_a_file_not_matching_its_name_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let (source, uploads) = environment(&dir, "source").await;
+        let forged = format!("{}.pdf", "0".repeat(64));
+        add_media(&source, &uploads, &forged, b"%PDF-1.4 document").await;
+        let (_, storage) = environment(&dir, "target").await;
+        assert!(read_archive(archive(&source, &uploads).await, &storage).is_err());
+
+        let (source, uploads) = environment(&dir, "script").await;
+        let script = format!("{}.pdf", "1".repeat(64));
+        add_media(&source, &uploads, &script, b"<script>alert(1)</script>").await;
+        assert!(read_archive(archive(&source, &uploads).await, &storage).is_err());
+        assert_eq!(fs::read_dir(storage.root()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn rejected_archive_writes_no_files() {
+        let dir = TempDir::new().unwrap();
+        let (source, uploads) = environment(&dir, "source").await;
+        let cover = cover(&uploads);
+        add_post(&source, 1, "Первый", "first", &cover).await;
+        let mut export = export(&source).await.ok().unwrap();
+        export.version = VERSION + 1;
+        let mut archive = Cursor::new(Vec::new());
+        assert!(write_archive(&mut archive, &export, &uploads).is_ok());
+        let (target, storage) = environment(&dir, "target").await;
+        archive.set_position(0);
+        let (export, _) = read_archive(archive, &storage).ok().unwrap();
+        assert!(import(&target, export).await.is_err());
+        assert!(!storage.root().join(&cover).exists());
+    }
+}
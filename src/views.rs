@@ -3,13 +3,26 @@ use axum::{
     http::StatusCode,
     response::{Html, IntoResponse, Response},
 };
+use serde::Deserialize;
+
+use crate::HistoryError;
 
 pub mod admin;
+pub mod api;
+pub mod backups;
 pub mod books;
+pub mod bulk;
+pub mod dashboard;
 pub mod handlers;
+pub mod media;
 pub mod posts;
 pub mod publications;
+pub mod stats;
+pub mod terms;
 pub mod texts;
+pub mod tokens;
+pub mod transfers;
+pub mod uploads;
 
 pub struct HtmlTemplate<T>(pub T);
 impl<T> IntoResponse for HtmlTemplate<T>
@@ -27,3 +40,39 @@ where
         }
     }
 }
+
+// Every entry of a catalog section, in the order set by dragging them in
+// the admin list.
+#[derive(Deserialize)]
+pub struct Ordering {
+    pub ids: Vec<u32>,
+}
+
+impl Ordering {
+    // For the list's data attribute, which the script splices the new order
+    // of the shown page into
+    pub fn attribute(ids: &[u32]) -> String {
+        ids.iter().map(u32::to_string).collect::<Vec<_>>().join(",")
+    }
+
+    pub fn saved(done: bool) -> Result<StatusCode, HistoryError> {
+        match done {
+            true => Ok(StatusCode::NO_CONTENT),
+            false => Err(HistoryError::Invalid(
+                "Список изменился, обновите страницу и попробуйте снова".to_string(),
+            )),
+        }
+    }
+}
+
+// Catalog links end up in the Location header of `/download`, which can't
+// carry line breaks and other control characters
+pub fn check_link(link: String) -> Result<String, HistoryError> {
+    let link = link.trim();
+    if link.chars().any(char::is_control) {
+        return Err(HistoryError::Invalid(
+            "Ссылка не может содержать переводы строк и управляющие символы".to_string(),
+        ));
+    }
+    Ok(link.to_string())
+}
@@ -6,7 +6,7 @@ use axum::{
 };
 use std::sync::Arc;
 
-use crate::{Auth, HistoryState, HtmlTemplate, LoginInput, User};
+use crate::{auth, models::user::User, Auth, HistoryState, HtmlTemplate, LoginInput};
 
 #[derive(Template)]
 #[template(path = "admin/login.html")]
@@ -21,12 +21,12 @@ pub async fn login(
     State(state): State<Arc<HistoryState>>,
     Form(input): Form<LoginInput>,
 ) -> impl IntoResponse {
-    if state.secret.eq(&input.secret) {
-        let user = User::new(input.secret);
-        auth.login(&user).await.unwrap();
-        Redirect::to("/books")
-    } else {
-        Redirect::to("/login")
+    match User::fetch_by_name(&state.db, input.name.trim()).await {
+        Ok(user) if auth::verify_password(&input.password, &user.password_hash) => {
+            auth.login(&user).await.unwrap();
+            Redirect::to("/admin")
+        }
+        _ => Redirect::to("/login"),
     }
 }
 
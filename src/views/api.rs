@@ -0,0 +1,470 @@
+use axum::{
+    body::HttpBody,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, put, MethodRouter},
+    BoxError, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{Api, Site},
+    markup::{self, footnotes::Note, Format, Rendered},
+    models::{
+        source::{NewSource, Source},
+        tag::Tag,
+        term::Facets,
+        Page, Pagination,
+    },
+    Book, HistoryError, HistoryState, Post, Publication, Text,
+};
+
+pub mod docs;
+mod write;
+
+pub fn router<B>(settings: &Api) -> Router<Arc<HistoryState>, B>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    routes()
+        .into_iter()
+        .fold(Router::new(), |router, (path, methods)| {
+            router.route(path, methods)
+        })
+        .fallback(nothing)
+        .layer(cors(settings))
+}
+
+// All routes of the API in one table, which the docs test checks against
+// the spec.
+pub(crate) fn routes<B>() -> Vec<(&'static str, MethodRouter<Arc<HistoryState>, B>)>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    vec![
+        ("/posts", get(posts).post(write::create_post)),
+        (
+            "/posts/:key",
+            get(post).put(write::update_post).delete(write::delete_post),
+        ),
+        ("/books", get(books).post(write::create_book)),
+        (
+            "/books/:id",
+            put(write::update_book).delete(write::delete_book),
+        ),
+        (
+            "/publications",
+            get(publications).post(write::create_publication),
+        ),
+        (
+            "/publications/:id",
+            put(write::update_publication).delete(write::delete_publication),
+        ),
+        ("/texts", get(texts).post(write::create_text)),
+        (
+            "/texts/:id",
+            put(write::update_text).delete(write::delete_text),
+        ),
+    ]
+}
+
+// Without configured origins browsers on other sites can't read the API,
+// servers and bots are not affected.
+fn cors(settings: &Api) -> CorsLayer {
+    let origins = if settings.cors_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            settings
+                .cors_origins
+                .iter()
+                .filter_map(|origin| origin.parse::<HeaderValue>().ok()),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+        ])
+        .allow_headers([
+            header::IF_NONE_MATCH,
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+        ])
+        .expose_headers([header::ETAG])
+}
+
+pub struct ApiError(HistoryError);
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: self.0.message(),
+        };
+        let mut response = (self.0.status(), Json(body)).into_response();
+        if let HistoryError::Unauthorized = self.0 {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+impl<E: Into<HistoryError>> From<E> for ApiError {
+    fn from(error: E) -> Self {
+        ApiError(error.into())
+    }
+}
+
+async fn nothing() -> ApiError {
+    ApiError(HistoryError::NotFound)
+}
+
+// The tag is a hash of the body, so it changes exactly when the content
+// does and clients can revalidate with If-None-Match.
+fn respond<T: Serialize>(headers: &HeaderMap, value: &T) -> Result<Response, ApiError> {
+    let body = serde_json::to_vec(value).map_err(|_| HistoryError::InternalError)?;
+    let etag = format!("\"{:x}\"", Sha256::digest(&body));
+    let matches = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*")
+        });
+    if matches {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::ETAG, etag),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Cover {
+    pub original: String,
+    pub thumb: String,
+    pub og: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostSummary {
+    pub id: u32,
+    pub slug: String,
+    pub title: String,
+    pub lead: String,
+    pub cover: Option<Cover>,
+    pub created_at: DateTime<Utc>,
+    pub published_at: DateTime<Utc>,
+    pub url: String,
+}
+
+impl PostSummary {
+    fn new(post: &Post, site: &Site) -> Self {
+        let cover = (!post.cover.is_empty()).then(|| Cover {
+            original: format!("{}/static/uploads/{}", site.url, post.cover),
+            thumb: format!(
+                "{}/static/uploads/{}",
+                site.url,
+                post.cover_variant("thumb", "webp")
+            ),
+            og: format!(
+                "{}/static/uploads/{}",
+                site.url,
+                post.cover_variant("og", "jpg")
+            ),
+        });
+        Self {
+            id: post.id,
+            slug: post.slug.clone(),
+            title: post.title.clone(),
+            lead: post.lead.clone(),
+            cover,
+            created_at: post.created_at,
+            published_at: post.published_at,
+            url: format!("{}/entry/{}", site.url, post.id),
+        }
+    }
+}
+
+// A book, publication or text as the public lists show it: drafts don't
+// get there, positions only decide the order and download counts are left
+// out unless the site shows them.
+#[derive(Serialize, ToSchema)]
+pub struct CatalogEntry {
+    pub id: u32,
+    pub name: String,
+    pub link: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downloads: Option<i64>,
+}
+
+impl CatalogEntry {
+    fn list<T: Into<CatalogEntry>>(entries: Vec<T>, site: &Site) -> Vec<Self> {
+        entries
+            .into_iter()
+            .map(Into::into)
+            .map(|entry: CatalogEntry| Self {
+                downloads: entry.downloads.filter(|_| site.show_downloads),
+                ..entry
+            })
+            .collect()
+    }
+}
+
+impl From<Book> for CatalogEntry {
+    fn from(book: Book) -> Self {
+        Self {
+            id: book.id,
+            name: book.name,
+            link: book.link,
+            description: book.description,
+            downloads: Some(book.downloads),
+        }
+    }
+}
+
+impl From<Publication> for CatalogEntry {
+    fn from(publication: Publication) -> Self {
+        Self {
+            id: publication.id,
+            name: publication.name,
+            link: publication.link,
+            description: publication.description,
+            downloads: Some(publication.downloads),
+        }
+    }
+}
+
+impl From<Text> for CatalogEntry {
+    fn from(text: Text) -> Self {
+        Self {
+            id: text.id,
+            name: text.name,
+            link: text.link,
+            description: text.description,
+            downloads: Some(text.downloads),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostDetail {
+    #[serde(flatten)]
+    pub summary: PostSummary,
+    pub format: Format,
+    // The text as written and as shown on the site
+    pub body: String,
+    pub html: String,
+    pub notes: Vec<Note>,
+    pub sources: Vec<NewSource>,
+    pub tags: Vec<Tag>,
+}
+
+impl PostDetail {
+    async fn new(state: &HistoryState, post: Post) -> Result<Self, HistoryError> {
+        let Rendered { body: html, notes } = markup::render(post.format, &post.body);
+        let sources = Source::list(&state.db, post.id)
+            .await?
+            .into_iter()
+            .map(NewSource::from)
+            .collect();
+        let tags = Tag::for_post(&state.db, post.id).await?;
+        Ok(Self {
+            summary: PostSummary::new(&post, &state.site),
+            format: post.format,
+            body: post.body,
+            html,
+            notes,
+            sources,
+            tags,
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts",
+    tag = "posts",
+    params(
+        ("page" = Option<u32>, Query, description = "Page number, from 1"),
+        ("per_page" = Option<u32>, Query, description = "Posts per page, at most 100"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response"),
+    ),
+    responses(
+        (status = 200, description = "Posts, newest first", body = Page<PostSummary>),
+        (status = 304, description = "Not modified since the given ETag"),
+    )
+)]
+pub async fn posts(
+    headers: HeaderMap,
+    Query(pagination): Query<Pagination>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    let page = Post::page_faceted(&state.db, &Facets::default(), pagination)
+        .await?
+        .map(|post| PostSummary::new(&post, &state.site));
+    respond(&headers, &page)
+}
+
+// Numeric keys are ids, which is why slugs can't be all digits.
+async fn find_post(db: &SqlitePool, key: &str) -> Result<Post, HistoryError> {
+    match key.parse::<u32>() {
+        Ok(id) => Ok(Post::fetch(db, id).await?),
+        Err(_) => Ok(Post::fetch_by_slug(db, key).await?),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/{key}",
+    tag = "posts",
+    params(
+        ("key" = String, Path, description = "Id or slug of the post"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response"),
+    ),
+    responses(
+        (status = 200, description = "The post", body = PostDetail),
+        (status = 304, description = "Not modified since the given ETag"),
+        (status = 404, description = "No such post", body = ErrorBody),
+    )
+)]
+pub async fn post(
+    headers: HeaderMap,
+    Path(key): Path<String>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    let post = find_post(&state.db, &key).await?;
+    if post.draft {
+        return Err(HistoryError::NotFound.into());
+    }
+    respond(&headers, &PostDetail::new(&state, post).await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/books",
+    tag = "catalog",
+    params(("If-None-Match" = Option<String>, Header, description = "ETag of a previous response")),
+    responses(
+        (status = 200, description = "Scientific books and articles", body = Vec<CatalogEntry>),
+        (status = 304, description = "Not modified since the given ETag"),
+    )
+)]
+pub async fn books(
+    headers: HeaderMap,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    let books = Book::published(&state.db).await?;
+    respond(&headers, &CatalogEntry::list(books, &state.site))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/publications",
+    tag = "catalog",
+    params(("If-None-Match" = Option<String>, Header, description = "ETag of a previous response")),
+    responses(
+        (status = 200, description = "Journalism", body = Vec<CatalogEntry>),
+        (status = 304, description = "Not modified since the given ETag"),
+    )
+)]
+pub async fn publications(
+    headers: HeaderMap,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    let publications = Publication::published(&state.db).await?;
+    respond(&headers, &CatalogEntry::list(publications, &state.site))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/texts",
+    tag = "catalog",
+    params(("If-None-Match" = Option<String>, Header, description = "ETag of a previous response")),
+    responses(
+        (status = 200, description = "Prose and poetry", body = Vec<CatalogEntry>),
+        (status = 304, description = "Not modified since the given ETag"),
+    )
+)]
+pub async fn texts(
+    headers: HeaderMap,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    let texts = Text::published(&state.db).await?;
+    respond(&headers, &CatalogEntry::list(texts, &state.site))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analytics::Analytics, config::Backup, models, storage::Storage};
+    use serde_json::{json, Value};
+    use sqlx::query;
+
+    async fn listed_books(show_downloads: bool) -> Value {
+        let db = models::memory().await;
+        query(
+            "INSERT INTO books (name, link, description, position, draft, downloads) \
+             VALUES ('Книга', 'https://example.com', '', 1, 0, 7), ('Черновик', '', '', 2, 1, 0)",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        let state = HistoryState {
+            db,
+            storage: Storage::new("uploads"),
+            site: Site {
+                show_downloads,
+                ..Site::default()
+            },
+            backup: Backup::default(),
+            analytics: Analytics::default(),
+        };
+        let response = books(HeaderMap::new(), State(Arc::new(state)))
+            .await
+            .ok()
+            .unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn catalog_shows_downloads_only_where_the_site_does() {
+        let entry =
+            json!({"id": 1, "name": "Книга", "link": "https://example.com", "description": ""});
+        assert_eq!(listed_books(false).await, json!([entry]));
+        let mut counted = entry;
+        counted["downloads"] = json!(7);
+        assert_eq!(listed_books(true).await, json!([counted]));
+    }
+}
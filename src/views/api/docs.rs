@@ -0,0 +1,285 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use std::sync::Arc;
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use super::{write, CatalogEntry, Cover, ErrorBody, PostDetail, PostSummary};
+use crate::{
+    markup::{footnotes::Note, Format},
+    models::{
+        book::NewBook, publication::NewPublication, source::NewSource, tag::Tag, text::NewText,
+        Page,
+    },
+    Book, Publication, Text,
+};
+
+// The document is built from the handlers' `#[utoipa::path]` attributes and
+// the types they return; the test below keeps it in step with the router.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "History API",
+        description = "Posts and the catalog of books, publications and texts"
+    ),
+    paths(
+        super::posts,
+        super::post,
+        super::books,
+        super::publications,
+        super::texts,
+        write::create_post,
+        write::update_post,
+        write::delete_post,
+        write::create_book,
+        write::update_book,
+        write::delete_book,
+        write::create_publication,
+        write::update_publication,
+        write::delete_publication,
+        write::create_text,
+        write::update_text,
+        write::delete_text,
+    ),
+    components(schemas(
+        Page<PostSummary>,
+        PostSummary,
+        PostDetail,
+        Cover,
+        Note,
+        Format,
+        NewSource,
+        Tag,
+        CatalogEntry,
+        write::PostInput,
+        Book,
+        NewBook,
+        Publication,
+        NewPublication,
+        Text,
+        NewText,
+        ErrorBody,
+    )),
+    modifiers(&TokenAuth),
+    tags(
+        (name = "posts", description = "Posts of the blog"),
+        (name = "catalog", description = "Books, publications and texts")
+    )
+)]
+pub struct ApiDoc;
+
+struct TokenAuth;
+
+impl Modify for TokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+        }
+    }
+}
+
+pub async fn spec() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+pub async fn redirect() -> Redirect {
+    Redirect::permanent("/api/docs/")
+}
+
+pub async fn index() -> Response {
+    swagger_file("index.html")
+}
+
+pub async fn viewer(Path(path): Path<String>) -> Response {
+    swagger_file(&path)
+}
+
+// Swagger UI is compiled into the binary, so the viewer works without
+// access to a CDN.
+fn swagger_file(path: &str) -> Response {
+    let config = Arc::new(utoipa_swagger_ui::Config::from("/api/openapi.json"));
+    match utoipa_swagger_ui::serve(path, config) {
+        Ok(Some(file)) => (
+            [(header::CONTENT_TYPE, file.content_type)],
+            file.bytes.to_vec(),
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analytics::Analytics,
+        config::{Api, Backup, Site},
+        models::MIGRATOR,
+        storage::Storage,
+        HistoryState,
+    };
+    use axum::{
+        body::Body,
+        extract::MatchedPath,
+        http::{HeaderValue, Method, Request},
+        middleware::{self, Next},
+        Router,
+    };
+    use sqlx::sqlite::SqlitePoolOptions;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+    use utoipa::openapi::path::PathItem;
+
+    const MATCHED: &str = "x-matched-path";
+    // Routed requests come back with the route they matched, requests
+    // that fell through to the fallback without it.
+    async fn mark(matched: MatchedPath, request: Request<Body>, next: Next<Body>) -> Response {
+        let mut response = next.run(request).await;
+        let matched = HeaderValue::from_str(matched.as_str()).unwrap();
+        response.headers_mut().insert(MATCHED, matched);
+        response
+    }
+
+    async fn router() -> (Router, TempDir) {
+        let uploads = TempDir::new().unwrap();
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        MIGRATOR.run(&db).await.unwrap();
+        let state = HistoryState {
+            db,
+            storage: Storage::new(uploads.path()),
+            site: Site::default(),
+            backup: Backup::default(),
+            analytics: Analytics::default(),
+        };
+        let api = super::super::router(&Api::default()).route_layer(middleware::from_fn(mark));
+        let router = Router::new()
+            .nest("/api/v1", api)
+            .with_state(Arc::new(state));
+        (router, uploads)
+    }
+
+    fn operations(item: &PathItem) -> Vec<Method> {
+        [
+            (Method::GET, &item.get),
+            (Method::POST, &item.post),
+            (Method::PUT, &item.put),
+            (Method::DELETE, &item.delete),
+        ]
+        .into_iter()
+        .filter(|(_, operation)| operation.is_some())
+        .map(|(method, _)| method)
+        .collect()
+    }
+
+    // "/api/v1/posts/{key}" is routed as "/api/v1/posts/:key" and requested
+    // as "/api/v1/posts/1"
+    fn route_and_uri(path: &str) -> (String, String) {
+        let segments = path.split('/').map(|segment| {
+            match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(name) => (format!(":{}", name), "1".to_string()),
+                None => (segment.to_string(), segment.to_string()),
+            }
+        });
+        let (route, uri): (Vec<_>, Vec<_>) = segments.unzip();
+        (route.join("/"), uri.join("/"))
+    }
+
+    async fn request(router: &Router, method: Method, uri: &str) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{}"))
+            .unwrap();
+        router.clone().oneshot(request).await.unwrap()
+    }
+
+    #[test]
+    fn is_openapi_3_1() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert_eq!(spec["openapi"], "3.1.0");
+    }
+
+    #[tokio::test]
+    async fn every_documented_operation_is_routed() {
+        let (router, _uploads) = router().await;
+        for (path, item) in ApiDoc::openapi().paths.paths.iter() {
+            let (route, uri) = route_and_uri(path);
+            for method in operations(item) {
+                let response = request(&router, method.clone(), &uri).await;
+                assert_ne!(
+                    response.status(),
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "{} {} is documented but not routed",
+                    method,
+                    path
+                );
+                assert_eq!(
+                    response
+                        .headers()
+                        .get(MATCHED)
+                        .map(|value| value.to_str().unwrap()),
+                    Some(route.as_str()),
+                    "{} {} is documented but not routed",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn every_routed_method_is_documented() {
+        let (router, _uploads) = router().await;
+        for (path, item) in ApiDoc::openapi().paths.paths.iter() {
+            let (_, uri) = route_and_uri(path);
+            let documented = operations(item);
+            for method in [Method::GET, Method::POST, Method::PUT, Method::DELETE] {
+                if documented.contains(&method) {
+                    continue;
+                }
+                let response = request(&router, method.clone(), &uri).await;
+                assert_eq!(
+                    response.status(),
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "{} {} is routed but not documented",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    // The router is built from the same table, so a route added there can't
+    // be left out of the spec
+    #[test]
+    fn every_route_is_documented() {
+        let mut routes: Vec<String> = super::super::routes::<Body>()
+            .into_iter()
+            .map(|(path, _)| format!("/api/v1{}", path))
+            .collect();
+        routes.sort();
+        let mut documented: Vec<String> = ApiDoc::openapi()
+            .paths
+            .paths
+            .keys()
+            .map(|path| route_and_uri(path).0)
+            .collect();
+        documented.sort();
+        assert_eq!(routes, documented, "routed and documented paths differ");
+    }
+}
@@ -0,0 +1,669 @@
+use axum::{
+    async_trait,
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        FromRequestParts, Path, State,
+    },
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use super::{find_post, ApiError, ErrorBody, PostDetail};
+use crate::{
+    auth::{self, Scope},
+    markup::{self, Format},
+    models::{
+        book::NewBook,
+        media::Media,
+        post::NewPost,
+        publication::NewPublication,
+        source::{NewSource, Source},
+        tag::{self, Tag},
+        text::NewText,
+        token::Token,
+    },
+    views::{
+        check_link,
+        posts::{check_body, check_sources, choose_slug, remove_cover},
+    },
+    Book, HistoryError, HistoryState, Post, Publication, Text,
+};
+
+// `Authorization: Bearer <token>`; every successful use is recorded.
+pub struct ApiToken(pub Token);
+
+impl ApiToken {
+    fn require(&self, scope: Scope) -> Result<(), ApiError> {
+        if self.0.allows(scope) {
+            Ok(())
+        } else {
+            Err(ApiError(HistoryError::Forbidden))
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<HistoryState>> for ApiToken {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<HistoryState>,
+    ) -> Result<Self, Self::Rejection> {
+        let secret = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(HistoryError::Unauthorized)?;
+        let token = match Token::fetch_by_hash(&state.db, &auth::hash_token(secret.trim())).await {
+            Ok(token) if !token.is_expired() => token,
+            Ok(_) | Err(sqlx::Error::RowNotFound) => return Err(HistoryError::Unauthorized.into()),
+            Err(error) => return Err(error.into()),
+        };
+        Token::touch(&state.db, token.id).await?;
+        Ok(ApiToken(token))
+    }
+}
+
+fn payload<T>(payload: Result<Json<T>, JsonRejection>) -> Result<T, ApiError> {
+    payload
+        .map(|Json(value)| value)
+        .map_err(|rejection| ApiError(HistoryError::Invalid(rejection.body_text())))
+}
+
+// A key axum can't parse names no entry, and is reported as JSON like any
+// other error rather than in axum's plain text
+fn path<T>(path: Result<Path<T>, PathRejection>) -> Result<T, ApiError> {
+    path.map(|Path(value)| value)
+        .map_err(|_| ApiError(HistoryError::NotFound))
+}
+
+// HTML from the admin editor is trusted as is, but a token can leak, so
+// HTML sent with one is cleaned the way Markdown output is
+fn clean(format: Format, body: String) -> String {
+    match format {
+        Format::Html => markup::sanitize(&body),
+        Format::Markdown => body,
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PostInput {
+    pub title: String,
+    // Made from the title when left out
+    #[serde(default)]
+    pub slug: String,
+    pub lead: String,
+    pub body: String,
+    #[serde(default)]
+    pub format: Format,
+    // Left as they are on update when not given
+    pub sources: Option<Vec<NewSource>>,
+    pub tags: Option<Vec<String>>,
+    // Now on create and unchanged on update when not given
+    pub published_at: Option<DateTime<Utc>>,
+    // Published on create and unchanged on update when not given
+    pub draft: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts",
+    tag = "posts",
+    request_body = PostInput,
+    responses(
+        (status = 201, description = "The created post", body = PostDetail),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 422, description = "Invalid input", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn create_post(
+    token: ApiToken,
+    State(state): State<Arc<HistoryState>>,
+    input: Result<Json<PostInput>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Posts)?;
+    let input = payload(input)?;
+    check_body(input.format, &input.body)?;
+    check_sources(input.sources.as_deref().unwrap_or_default())?;
+    let slug = choose_slug(&state.db, &input.slug, &input.title, 0).await?;
+    let new_post = NewPost {
+        title: input.title,
+        lead: input.lead,
+        body: clean(input.format, input.body),
+        format: input.format,
+        cover: String::new(),
+        slug,
+        published_at: input.published_at,
+        draft: input.draft,
+    };
+    let tags = tag::parse(&input.tags.unwrap_or_default().join(","));
+    let mut tx = state.db.begin().await?;
+    let id = Post::create_in(&mut tx, new_post)
+        .await?
+        .last_insert_rowid() as u32;
+    Source::replace_in(&mut tx, id, input.sources.unwrap_or_default()).await?;
+    Tag::replace_in(&mut tx, id, tags).await?;
+    tx.commit().await?;
+    let post = Post::fetch(&state.db, id).await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(PostDetail::new(&state, post).await?),
+    )
+        .into_response())
+}
+
+// Covers are managed in the admin, the API keeps whatever the post has.
+#[utoipa::path(
+    put,
+    path = "/api/v1/posts/{key}",
+    tag = "posts",
+    params(("key" = String, Path, description = "Id or slug of the post")),
+    request_body = PostInput,
+    responses(
+        (status = 200, description = "The updated post", body = PostDetail),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 404, description = "No such entry", body = ErrorBody),
+        (status = 422, description = "Invalid input", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn update_post(
+    token: ApiToken,
+    key: Result<Path<String>, PathRejection>,
+    State(state): State<Arc<HistoryState>>,
+    input: Result<Json<PostInput>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Posts)?;
+    let input = payload(input)?;
+    let old_post = find_post(&state.db, &path(key)?).await?;
+    let id = old_post.id;
+    check_body(input.format, &input.body)?;
+    check_sources(input.sources.as_deref().unwrap_or_default())?;
+    let slug = choose_slug(&state.db, &input.slug, &input.title, id).await?;
+    let updated_post = NewPost {
+        title: input.title,
+        lead: input.lead,
+        body: clean(input.format, input.body),
+        format: input.format,
+        cover: old_post.cover,
+        slug,
+        published_at: input.published_at,
+        draft: input.draft,
+    };
+    let mut tx = state.db.begin().await?;
+    Post::update_in(&mut tx, id, updated_post).await?;
+    if let Some(sources) = input.sources {
+        Source::replace_in(&mut tx, id, sources).await?;
+    }
+    if let Some(tags) = input.tags {
+        Tag::replace_in(&mut tx, id, tag::parse(&tags.join(","))).await?;
+    }
+    tx.commit().await?;
+    let post = Post::fetch(&state.db, id).await?;
+    Ok(Json(PostDetail::new(&state, post).await?).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/posts/{key}",
+    tag = "posts",
+    params(("key" = String, Path, description = "Id or slug of the post")),
+    responses(
+        (status = 204, description = "The post is deleted"),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 404, description = "No such entry", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn delete_post(
+    token: ApiToken,
+    key: Result<Path<String>, PathRejection>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Posts)?;
+    let post = find_post(&state.db, &path(key)?).await?;
+    Post::delete(&state.db, post.id).await?;
+    if !Media::in_use(&state.db, &post.cover).await? {
+        remove_cover(&state.storage, &post.cover);
+    }
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/books",
+    tag = "catalog",
+    request_body = NewBook,
+    responses(
+        (status = 201, description = "The created entry", body = Book),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 422, description = "Invalid input", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn create_book(
+    token: ApiToken,
+    State(state): State<Arc<HistoryState>>,
+    input: Result<Json<NewBook>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let mut input = payload(input)?;
+    input.link = check_link(input.link)?;
+    let id = Book::create(&state.db, input).await?.last_insert_rowid() as u32;
+    let book = Book::fetch(&state.db, id).await?;
+    Ok((StatusCode::CREATED, Json(book)).into_response())
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/books/{id}",
+    tag = "catalog",
+    params(("id" = u32, Path, description = "Id of the entry")),
+    request_body = NewBook,
+    responses(
+        (status = 200, description = "The updated entry", body = Book),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 404, description = "No such entry", body = ErrorBody),
+        (status = 422, description = "Invalid input", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn update_book(
+    token: ApiToken,
+    id: Result<Path<u32>, PathRejection>,
+    State(state): State<Arc<HistoryState>>,
+    input: Result<Json<NewBook>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let id = path(id)?;
+    Book::fetch(&state.db, id).await?;
+    let mut input = payload(input)?;
+    input.link = check_link(input.link)?;
+    Book::update(&state.db, id, input).await?;
+    Ok(Json(Book::fetch(&state.db, id).await?).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/books/{id}",
+    tag = "catalog",
+    params(("id" = u32, Path, description = "Id of the entry")),
+    responses(
+        (status = 204, description = "The entry is deleted"),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 404, description = "No such entry", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn delete_book(
+    token: ApiToken,
+    id: Result<Path<u32>, PathRejection>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let id = path(id)?;
+    Book::fetch(&state.db, id).await?;
+    Book::delete(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/publications",
+    tag = "catalog",
+    request_body = NewPublication,
+    responses(
+        (status = 201, description = "The created entry", body = Publication),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 422, description = "Invalid input", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn create_publication(
+    token: ApiToken,
+    State(state): State<Arc<HistoryState>>,
+    input: Result<Json<NewPublication>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let mut input = payload(input)?;
+    input.link = check_link(input.link)?;
+    let id = Publication::create(&state.db, input)
+        .await?
+        .last_insert_rowid() as u32;
+    let publication = Publication::fetch(&state.db, id).await?;
+    Ok((StatusCode::CREATED, Json(publication)).into_response())
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/publications/{id}",
+    tag = "catalog",
+    params(("id" = u32, Path, description = "Id of the entry")),
+    request_body = NewPublication,
+    responses(
+        (status = 200, description = "The updated entry", body = Publication),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 404, description = "No such entry", body = ErrorBody),
+        (status = 422, description = "Invalid input", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn update_publication(
+    token: ApiToken,
+    id: Result<Path<u32>, PathRejection>,
+    State(state): State<Arc<HistoryState>>,
+    input: Result<Json<NewPublication>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let id = path(id)?;
+    Publication::fetch(&state.db, id).await?;
+    let mut input = payload(input)?;
+    input.link = check_link(input.link)?;
+    Publication::update(&state.db, id, input).await?;
+    Ok(Json(Publication::fetch(&state.db, id).await?).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/publications/{id}",
+    tag = "catalog",
+    params(("id" = u32, Path, description = "Id of the entry")),
+    responses(
+        (status = 204, description = "The entry is deleted"),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 404, description = "No such entry", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn delete_publication(
+    token: ApiToken,
+    id: Result<Path<u32>, PathRejection>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let id = path(id)?;
+    Publication::fetch(&state.db, id).await?;
+    Publication::delete(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/texts",
+    tag = "catalog",
+    request_body = NewText,
+    responses(
+        (status = 201, description = "The created entry", body = Text),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 422, description = "Invalid input", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn create_text(
+    token: ApiToken,
+    State(state): State<Arc<HistoryState>>,
+    input: Result<Json<NewText>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let mut input = payload(input)?;
+    input.link = check_link(input.link)?;
+    let id = Text::create(&state.db, input).await?.last_insert_rowid() as u32;
+    let text = Text::fetch(&state.db, id).await?;
+    Ok((StatusCode::CREATED, Json(text)).into_response())
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/texts/{id}",
+    tag = "catalog",
+    params(("id" = u32, Path, description = "Id of the entry")),
+    request_body = NewText,
+    responses(
+        (status = 200, description = "The updated entry", body = Text),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 404, description = "No such entry", body = ErrorBody),
+        (status = 422, description = "Invalid input", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn update_text(
+    token: ApiToken,
+    id: Result<Path<u32>, PathRejection>,
+    State(state): State<Arc<HistoryState>>,
+    input: Result<Json<NewText>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let id = path(id)?;
+    Text::fetch(&state.db, id).await?;
+    let mut input = payload(input)?;
+    input.link = check_link(input.link)?;
+    Text::update(&state.db, id, input).await?;
+    Ok(Json(Text::fetch(&state.db, id).await?).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/texts/{id}",
+    tag = "catalog",
+    params(("id" = u32, Path, description = "Id of the entry")),
+    responses(
+        (status = 204, description = "The entry is deleted"),
+        (status = 401, description = "Missing, unknown or expired token", body = ErrorBody),
+        (status = 403, description = "The token doesn't allow this", body = ErrorBody),
+        (status = 404, description = "No such entry", body = ErrorBody),
+    ),
+    security(("token" = []))
+)]
+pub async fn delete_text(
+    token: ApiToken,
+    id: Result<Path<u32>, PathRejection>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<Response, ApiError> {
+    token.require(Scope::Catalog)?;
+    let id = path(id)?;
+    Text::fetch(&state.db, id).await?;
+    Text::delete(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analytics::Analytics,
+        config::{Api, Backup, Site},
+        models::{user::User, MIGRATOR},
+        storage::Storage,
+    };
+    use axum::{
+        body::Body,
+        http::{Method, Request},
+        Router,
+    };
+    use chrono::Duration;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    async fn router() -> (Router, Arc<HistoryState>, TempDir) {
+        let uploads = TempDir::new().unwrap();
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        MIGRATOR.run(&db).await.unwrap();
+        User::create(&db, "admin", "hash").await.unwrap();
+        let state = Arc::new(HistoryState {
+            db,
+            storage: Storage::new(uploads.path()),
+            site: Site::default(),
+            backup: Backup::default(),
+            analytics: Analytics::default(),
+        });
+        let router = Router::new()
+            .nest("/api/v1", super::super::router(&Api::default()))
+            .with_state(state.clone());
+        (router, state, uploads)
+    }
+
+    // Returns the secret of the new token
+    async fn token(
+        state: &HistoryState,
+        scopes: &[Scope],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> String {
+        let user = User::fetch_by_name(&state.db, "admin").await.unwrap();
+        let secret = auth::generate_token();
+        Token::create(
+            &state.db,
+            user.id,
+            "test",
+            &auth::hash_token(&secret),
+            scopes,
+            expires_at,
+        )
+        .await
+        .unwrap();
+        secret
+    }
+
+    async fn send(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        secret: Option<&str>,
+        body: &str,
+    ) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = secret {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", secret));
+        }
+        router
+            .clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn create_book(router: &Router, secret: Option<&str>) -> StatusCode {
+        let body = r#"{"name": "Книга", "link": "https://example.com", "description": ""}"#;
+        send(router, Method::POST, "/api/v1/books", secret, body)
+            .await
+            .status()
+    }
+
+    #[tokio::test]
+    async fn missing_unknown_and_expired_tokens_are_unauthorized() {
+        let (router, state, _uploads) = router().await;
+        let expired = token(
+            &state,
+            &[Scope::Catalog],
+            Some(Utc::now() - Duration::days(1)),
+        )
+        .await;
+        assert_eq!(create_book(&router, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            create_book(&router, Some(&auth::generate_token())).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            create_book(&router, Some(&expired)).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn token_needs_the_scope() {
+        let (router, state, _uploads) = router().await;
+        let posts = token(&state, &[Scope::Posts], None).await;
+        let catalog = token(&state, &[Scope::Catalog], None).await;
+        assert_eq!(
+            create_book(&router, Some(&posts)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            create_book(&router, Some(&catalog)).await,
+            StatusCode::CREATED
+        );
+    }
+
+    #[tokio::test]
+    async fn html_from_a_token_is_cleaned() {
+        let (router, state, _uploads) = router().await;
+        let secret = token(&state, &[Scope::Posts], None).await;
+        let body = r#"{"title": "Запись", "lead": "", "format": "html",
+            "body": "<p onclick=\"steal()\">Текст</p><script>steal()</script>"}"#;
+        let response = send(&router, Method::POST, "/api/v1/posts", Some(&secret), body).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let post = Post::fetch(&state.db, 1).await.unwrap();
+        assert_eq!(post.body, "<p>Текст</p>");
+    }
+
+    #[tokio::test]
+    async fn source_links_must_be_http() {
+        let (router, state, _uploads) = router().await;
+        let secret = token(&state, &[Scope::Posts], None).await;
+        let body = r#"{"title": "Запись", "lead": "", "body": "", "sources": [{"author": "",
+            "title": "", "year": "", "archive": "", "fond": "", "opis": "", "delo": "",
+            "url": "javascript:steal()"}]}"#;
+        let response = send(&router, Method::POST, "/api/v1/posts", Some(&secret), body).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn posts_are_changed_by_id_or_slug_and_bad_ids_get_json() {
+        let (router, state, _uploads) = router().await;
+        let secret = token(&state, &[Scope::Posts, Scope::Catalog], None).await;
+        let body = r#"{"title": "Запись", "lead": "", "body": "", "slug": "entry"}"#;
+        let response = send(&router, Method::POST, "/api/v1/posts", Some(&secret), body).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = r#"{"title": "Другая", "lead": "", "body": "", "slug": "entry"}"#;
+        let response = send(
+            &router,
+            Method::PUT,
+            "/api/v1/posts/entry",
+            Some(&secret),
+            body,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(Post::fetch(&state.db, 1).await.unwrap().title, "Другая");
+        for uri in ["/api/v1/posts/missing", "/api/v1/books/abc"] {
+            let response = send(&router, Method::DELETE, uri, Some(&secret), "").await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        }
+        let response = send(
+            &router,
+            Method::DELETE,
+            "/api/v1/posts/entry",
+            Some(&secret),
+            "",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+}
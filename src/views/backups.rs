@@ -0,0 +1,71 @@
+use askama::Template;
+use axum::{
+    body::StreamBody,
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Redirect},
+};
+use std::sync::Arc;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    backup::{self, Snapshot},
+    config::Backup,
+    HistoryError, HistoryState, HtmlTemplate,
+};
+
+#[derive(Template)]
+#[template(path = "admin/backups.html")]
+pub struct BackupsTemplate {
+    pub snapshots: Vec<Snapshot>,
+    pub settings: Backup,
+}
+
+pub async fn all(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let snapshots = backup::list(&state.backup.dir)?;
+    Ok(HtmlTemplate(BackupsTemplate {
+        snapshots,
+        settings: state.backup.clone(),
+    }))
+}
+
+pub async fn create(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    backup::create(&state.db, &state.storage, &state.backup.dir).await?;
+    backup::rotate(&state.backup)?;
+    Ok(Redirect::to("/backups"))
+}
+
+pub async fn download(
+    Path((name, file)): Path<(String, String)>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let path = backup::file(&state.backup.dir, &name, &file)?;
+    let content_type = if file == backup::DATABASE {
+        "application/vnd.sqlite3"
+    } else {
+        "application/gzip"
+    };
+    let body = StreamBody::new(ReaderStream::new(tokio::fs::File::open(path).await?));
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}-{}\"", name, file),
+            ),
+        ],
+        body,
+    ))
+}
+
+pub async fn restore(
+    Path(name): Path<String>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    backup::restore(&state.db, &state.storage, &state.backup, &name).await?;
+    Ok(Redirect::to("/backups"))
+}
@@ -1,47 +1,110 @@
 use askama::Template;
 use axum::{
-    extract::{Form, Path, State},
+    extract::{Path, Query, State},
     response::{IntoResponse, Redirect},
+    Json,
 };
+use axum_extra::extract::Form;
+use serde::Deserialize;
 use std::sync::Arc;
 
 use crate::{
-    models::book::{Book, NewBook},
-    HistoryError, HistoryState, HtmlTemplate,
+    models::{
+        book::{Book, NewBook},
+        section::Section,
+        term::{Entry, Term},
+        Page, Pagination,
+    },
+    views::bulk::{self, Bulk, Choice},
+    views::check_link,
+    views::terms::TermChoice,
+    HistoryError, HistoryState, HtmlTemplate, Ordering,
 };
 
 #[derive(Template)]
 #[template(path = "admin/books/list.html")]
 pub struct BooksTemplate {
-    pub books: Vec<Book>,
+    pub page: Page<Book>,
+    pub order: String,
+    pub bulk: Choice,
 }
 
 #[derive(Template)]
 #[template(path = "admin/books/add.html")]
-pub struct NewBookTemplate;
+pub struct NewBookTemplate {
+    pub terms: TermChoice,
+}
 
 #[derive(Template)]
 #[template(path = "admin/books/edit.html")]
 pub struct EditBookTemplate {
     pub book: Book,
+    pub terms: TermChoice,
+}
+
+// The form also carries the ids of the checked terms, hence the form
+// extractor that collects repeated fields.
+#[derive(Deserialize)]
+pub struct BookForm {
+    pub name: String,
+    pub link: String,
+    pub description: String,
+    #[serde(default)]
+    pub terms: Vec<u32>,
+}
+
+impl BookForm {
+    fn split(self) -> Result<(NewBook, Vec<u32>), HistoryError> {
+        let book = NewBook {
+            name: self.name,
+            link: check_link(self.link)?,
+            description: self.description,
+        };
+        Ok((book, self.terms))
+    }
 }
 
 pub async fn all(
+    Query(pagination): Query<Pagination>,
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    let books = Book::list(&state.db).await?;
-    Ok(HtmlTemplate(BooksTemplate { books }))
+    let page = Book::page(&state.db, pagination).await?;
+    let order = Ordering::attribute(&Book::ids(&state.db).await?);
+    Ok(HtmlTemplate(BooksTemplate {
+        page,
+        order,
+        bulk: Choice::catalog(Section::Books),
+    }))
 }
 
-pub async fn add() -> impl IntoResponse {
-    HtmlTemplate(NewBookTemplate {})
+pub async fn bulk(
+    State(state): State<Arc<HistoryState>>,
+    Form(bulk): Form<Bulk>,
+) -> Result<impl IntoResponse, HistoryError> {
+    bulk::catalog(&state.db, Section::Books, bulk).await
+}
+
+pub async fn reorder(
+    State(state): State<Arc<HistoryState>>,
+    Json(ordering): Json<Ordering>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Ordering::saved(Book::reorder(&state.db, &ordering.ids).await?)
+}
+
+pub async fn add(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let terms = TermChoice::load(&state.db, None).await?;
+    Ok(HtmlTemplate(NewBookTemplate { terms }))
 }
 
 pub async fn create(
     State(state): State<Arc<HistoryState>>,
-    Form(new_book): Form<NewBook>,
+    Form(form): Form<BookForm>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    Book::create(&state.db, new_book).await?;
+    let (new_book, terms) = form.split()?;
+    let id = Book::create(&state.db, new_book).await?.last_insert_rowid() as u32;
+    Term::replace(&state.db, Entry::Book, id, &terms).await?;
     Ok(Redirect::to("/books"))
 }
 
@@ -50,15 +113,18 @@ pub async fn edit(
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
     let book = Book::fetch(&state.db, id).await?;
-    Ok(HtmlTemplate(EditBookTemplate { book }))
+    let terms = TermChoice::load(&state.db, Some((Entry::Book, id))).await?;
+    Ok(HtmlTemplate(EditBookTemplate { book, terms }))
 }
 
 pub async fn update(
     Path(id): Path<u32>,
     State(state): State<Arc<HistoryState>>,
-    Form(updated_book): Form<NewBook>,
+    Form(form): Form<BookForm>,
 ) -> Result<impl IntoResponse, HistoryError> {
+    let (updated_book, terms) = form.split()?;
     Book::update(&state.db, id, updated_book).await?;
+    Term::replace(&state.db, Entry::Book, id, &terms).await?;
     Ok(Redirect::to("/books"))
 }
 
@@ -0,0 +1,89 @@
+use axum::response::Redirect;
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use crate::{models::section::Section, HistoryError};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Delete,
+    Publish,
+    Unpublish,
+    Tag,
+    Untag,
+    Move,
+}
+
+// The entries checked in an admin list and what to do with them
+#[derive(Deserialize)]
+pub struct Bulk {
+    #[serde(default)]
+    pub ids: Vec<u32>,
+    pub action: Action,
+    // Comma-separated names, for tagging posts
+    #[serde(default)]
+    pub tags: String,
+    // For moving catalog entries
+    pub target: Option<Section>,
+}
+
+impl Bulk {
+    pub fn ids(&self) -> Result<&[u32], HistoryError> {
+        match self.ids.is_empty() {
+            true => Err(HistoryError::Invalid("Ничего не выбрано".to_string())),
+            false => Ok(&self.ids),
+        }
+    }
+}
+
+// What the toolbar above a list offers
+pub struct Choice {
+    pub tagging: bool,
+    // Where entries of this list can be moved
+    pub sections: Vec<Section>,
+}
+
+impl Choice {
+    pub fn posts() -> Self {
+        Choice {
+            tagging: true,
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn catalog(current: Section) -> Self {
+        Choice {
+            tagging: false,
+            sections: Section::ALL
+                .into_iter()
+                .filter(|section| *section != current)
+                .collect(),
+        }
+    }
+}
+
+pub async fn catalog(
+    db: &SqlitePool,
+    section: Section,
+    bulk: Bulk,
+) -> Result<Redirect, HistoryError> {
+    let ids = bulk.ids()?;
+    match bulk.action {
+        Action::Delete => section.delete(db, ids).await?,
+        Action::Publish => section.set_draft(db, ids, false).await?,
+        Action::Unpublish => section.set_draft(db, ids, true).await?,
+        Action::Move => {
+            let target = bulk
+                .target
+                .ok_or_else(|| HistoryError::Invalid("Выберите раздел".to_string()))?;
+            section.move_to(db, ids, target).await?
+        }
+        Action::Tag | Action::Untag => {
+            return Err(HistoryError::Invalid(
+                "Теги есть только у публикаций Школы".to_string(),
+            ))
+        }
+    }
+    Ok(Redirect::to(&format!("/{}", section.as_str())))
+}
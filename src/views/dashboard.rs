@@ -0,0 +1,38 @@
+use askama::Template;
+use axum::{extract::State, response::IntoResponse};
+use std::sync::Arc;
+
+use crate::{
+    consistency::{self, BrokenLink, Report, Usage},
+    models::overview::{self, Count, Item, Viewed},
+    HistoryError, HistoryState, HtmlTemplate,
+};
+
+const RECENT: u32 = 10;
+const MOST_VIEWED: u32 = 10;
+
+#[derive(Template)]
+#[template(path = "admin/dashboard.html")]
+pub struct DashboardTemplate {
+    pub counts: Vec<Count>,
+    pub recent: Vec<Item>,
+    pub drafts: Vec<Item>,
+    pub broken: Vec<BrokenLink>,
+    pub report: Report,
+    pub usage: Usage,
+    pub viewed: Vec<Viewed>,
+}
+
+pub async fn page(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Ok(HtmlTemplate(DashboardTemplate {
+        counts: overview::counts(&state.db).await?,
+        recent: overview::recent(&state.db, RECENT).await?,
+        drafts: overview::drafts(&state.db).await?,
+        broken: consistency::links(&state.db, &state.storage, &state.site).await?,
+        report: consistency::check(&state.db, &state.storage).await?,
+        usage: consistency::usage(&state.storage)?,
+        viewed: overview::most_viewed(&state.db, MOST_VIEWED).await?,
+    }))
+}
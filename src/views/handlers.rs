@@ -1,11 +1,26 @@
 use askama::Template;
 use axum::{
-    extract::{Path, State},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     response::IntoResponse,
 };
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
-use crate::{Book, HistoryError, HistoryState, HtmlTemplate, Post, Publication, Text};
+use crate::{
+    analytics,
+    config::Site,
+    markup::{self, footnotes::Note, Rendered},
+    models::{
+        post::{self, ArchiveYear},
+        section::Section,
+        source::Source,
+        tag::{Tag, TagCount},
+        term::{Entry, Facets, Panel, Term},
+        visit::Kind,
+        Page, Pagination,
+    },
+    Auth, Book, HistoryError, HistoryState, HtmlTemplate, Post, Publication, Text,
+};
 
 #[derive(Template)]
 #[template(path = "home.html")]
@@ -13,44 +28,298 @@ pub struct HomeTemplate {
     pub books: Vec<Book>,
     pub publications: Vec<Publication>,
     pub texts: Vec<Text>,
+    pub panel: Panel,
+    pub site: Site,
 }
 
 #[derive(Template)]
 #[template(path = "blog.html")]
 pub struct BlogTemplate {
+    pub page: Page<Post>,
+    // Set on a tag's archive page
+    pub tag: Option<Tag>,
+    pub cloud: Vec<TagCount>,
+    // Set on a year's or a month's archive page, e.g. "март 2024 года"
+    pub dated: Option<String>,
+    // Only shown on the main page of the blog
+    pub panel: Option<Panel>,
+    pub archive: Vec<ArchiveYear>,
+    pub site: Site,
+}
+
+#[derive(Template)]
+#[template(path = "rss.xml")]
+pub struct RssTemplate {
     pub posts: Vec<Post>,
+    pub tag: Option<Tag>,
+    pub site: Site,
+    // What links in the feed start with
+    pub base: String,
 }
 
 #[derive(Template)]
 #[template(path = "entry.html")]
 pub struct EntryTemplate {
     pub post: Post,
+    pub body: String,
+    pub notes: Vec<Note>,
+    pub sources: Vec<Source>,
+    pub tags: Vec<Tag>,
+    // One chain from the root down for every attached term
+    pub crumbs: Vec<Vec<Term>>,
+    pub site: Site,
 }
 
 pub async fn home(
+    Query(facets): Query<Facets>,
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    let books = Book::list(&state.db).await?;
-    let publications = Publication::list(&state.db).await?;
-    let texts = Text::list(&state.db).await?;
+    let books = Book::faceted(&state.db, &facets).await?;
+    let publications = Publication::faceted(&state.db, &facets).await?;
+    // Texts aren't classified, so a filter leaves none of them
+    let texts = if facets.is_empty() {
+        Text::published(&state.db).await?
+    } else {
+        Vec::new()
+    };
     Ok(HtmlTemplate(HomeTemplate {
         books,
         publications,
         texts,
+        panel: Panel::load(&state.db, facets).await?,
+        site: state.site.clone(),
     }))
 }
 
 pub async fn blog(
+    Query(pagination): Query<Pagination>,
+    Query(facets): Query<Facets>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let page = Post::page_faceted(&state.db, &facets, pagination)
+        .await?
+        .with_filter("period", &facets.period)
+        .with_filter("region", &facets.region);
+    Ok(HtmlTemplate(BlogTemplate {
+        page,
+        tag: None,
+        cloud: Tag::cloud(&state.db).await?,
+        dated: None,
+        panel: Some(Panel::load(&state.db, facets).await?),
+        archive: Post::archive(&state.db).await?,
+        site: state.site.clone(),
+    }))
+}
+
+pub async fn blog_tag(
+    Path(slug): Path<String>,
+    Query(pagination): Query<Pagination>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let tag = Tag::fetch_by_slug(&state.db, &slug).await?;
+    let page = Post::page_tagged(&state.db, tag.id, pagination).await?;
+    Ok(HtmlTemplate(BlogTemplate {
+        page,
+        tag: Some(tag),
+        cloud: Tag::cloud(&state.db).await?,
+        dated: None,
+        panel: None,
+        archive: Post::archive(&state.db).await?,
+        site: state.site.clone(),
+    }))
+}
+
+pub async fn blog_year(
+    Path(year): Path<String>,
+    Query(pagination): Query<Pagination>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    dated(&state, &year, None, pagination).await
+}
+
+pub async fn blog_month(
+    Path((year, month)): Path<(String, String)>,
+    Query(pagination): Query<Pagination>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    dated(&state, &year, Some(&month), pagination).await
+}
+
+// Anything that isn't a year or a month is a missing page rather than a
+// bad request, like any other unknown address.
+async fn dated(
+    state: &HistoryState,
+    year: &str,
+    month: Option<&str>,
+    pagination: Pagination,
+) -> Result<HtmlTemplate<BlogTemplate>, HistoryError> {
+    let year: i32 = year.parse().map_err(|_| HistoryError::NotFound)?;
+    if !(1..=9999).contains(&year) {
+        return Err(HistoryError::NotFound);
+    }
+    let month = match month {
+        Some(month) => match month.parse::<u32>() {
+            Ok(month) if (1..=12).contains(&month) => Some(month),
+            _ => return Err(HistoryError::NotFound),
+        },
+        None => None,
+    };
+    let title = match month {
+        Some(month) => format!("{} {} года", post::month_name(month).to_lowercase(), year),
+        None => format!("{} год", year),
+    };
+    let page = Post::page_dated(&state.db, year, month, pagination).await?;
+    Ok(HtmlTemplate(BlogTemplate {
+        page,
+        tag: None,
+        cloud: Tag::cloud(&state.db).await?,
+        dated: Some(title),
+        panel: None,
+        archive: Post::archive(&state.db).await?,
+        site: state.site.clone(),
+    }))
+}
+
+pub async fn rss(
+    headers: HeaderMap,
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    let posts = Post::list(&state.db).await?;
-    Ok(HtmlTemplate(BlogTemplate { posts }))
+    let posts = Post::page_faceted(&state.db, &Facets::default(), Pagination::default())
+        .await?
+        .items;
+    feed(RssTemplate {
+        posts,
+        tag: None,
+        site: state.site.clone(),
+        base: base_url(&state.site, &headers),
+    })
+}
+
+pub async fn tag_rss(
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let tag = Tag::fetch_by_slug(&state.db, &slug).await?;
+    let posts = Post::page_tagged(&state.db, tag.id, Pagination::default())
+        .await?
+        .items;
+    feed(RssTemplate {
+        posts,
+        tag: Some(tag),
+        site: state.site.clone(),
+        base: base_url(&state.site, &headers),
+    })
+}
+
+// Feed readers only take absolute links; without `site.url` they are made
+// from the host the feed was requested from
+fn base_url(site: &Site, headers: &HeaderMap) -> String {
+    if !site.url.is_empty() {
+        return site.url.clone();
+    }
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let scheme = match header("x-forwarded-proto") {
+        Some("https") => "https",
+        _ => "http",
+    };
+    format!(
+        "{}://{}",
+        scheme,
+        header(header::HOST.as_str()).unwrap_or("localhost")
+    )
+}
+
+fn feed(template: RssTemplate) -> Result<impl IntoResponse, HistoryError> {
+    let xml = template.render().map_err(|_| HistoryError::InternalError)?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        xml,
+    ))
 }
 
+// Drafts are shown to a signed in admin, so they can be reviewed in place
 pub async fn entry(
+    auth: Auth,
     Path(id): Path<u32>,
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
     let post = Post::fetch(&state.db, id).await?;
-    Ok(HtmlTemplate(EntryTemplate { post }))
+    if post.draft && auth.current_user.is_none() {
+        return Err(HistoryError::NotFound);
+    }
+    let Rendered { body, notes } = markup::render(post.format, &post.body);
+    let sources = Source::list(&state.db, id).await?;
+    let tags = Tag::for_post(&state.db, id).await?;
+    let mut crumbs = Vec::new();
+    for term in Term::for_entry(&state.db, Entry::Post, id).await? {
+        crumbs.push(Term::ancestors(&state.db, term.id).await?);
+    }
+    Ok(HtmlTemplate(EntryTemplate {
+        post,
+        body,
+        notes,
+        sources,
+        tags,
+        crumbs,
+        site: state.site.clone(),
+    }))
+}
+
+pub async fn sources_bibtex(
+    Path(id): Path<u32>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let post = Post::fetch(&state.db, id).await?;
+    if post.draft {
+        return Err(HistoryError::NotFound);
+    }
+    let bibtex = Source::list(&state.db, post.id)
+        .await?
+        .iter()
+        .map(Source::bibtex)
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "application/x-bibtex; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"post-{}.bib\"", post.id),
+            ),
+        ],
+        bibtex,
+    ))
+}
+
+// "Скачать" on the home page leads here rather than straight to the file,
+// so that following the link is counted; the admin's own clicks, crawlers
+// and HEAD requests are not.
+pub async fn download(
+    auth: Auth,
+    method: Method,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Path((kind, id)): Path<(String, u32)>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let section = Section::ALL
+        .into_iter()
+        .find(|section| section.as_str() == kind)
+        .ok_or(HistoryError::NotFound)?;
+    let link = section.download(&state.db, id).await?;
+    // Links saved before they were checked may still not fit in a header
+    let location = HeaderValue::try_from(link.as_str()).map_err(|_| HistoryError::NotFound)?;
+    if method != Method::HEAD && auth.current_user.is_none() && !analytics::is_bot(&headers) {
+        section.count_download(&state.db, id).await?;
+        let peer = peer.map(|ConnectInfo(peer)| peer);
+        state
+            .analytics
+            .record(&state.db, Kind::Download, &link, &headers, peer)
+            .await;
+    }
+    Ok((StatusCode::SEE_OTHER, [(header::LOCATION, location)]))
 }
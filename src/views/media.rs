@@ -0,0 +1,155 @@
+use askama::Template;
+use axum::{
+    body::Bytes,
+    extract::{Form, Path, Query, State},
+    response::{IntoResponse, Redirect},
+    Json,
+};
+use axum_typed_multipart::{FieldData, TryFromMultipart, TypedMultipart};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    images,
+    models::{
+        media::{Media, MediaDescription, NewMedia},
+        Page, Pagination,
+    },
+    storage::Storage,
+    HistoryError, HistoryState, HtmlTemplate, Post,
+};
+
+#[derive(TryFromMultipart)]
+pub struct MediaUpload {
+    pub file: FieldData<Bytes>,
+    pub alt: Option<String>,
+    pub caption: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MediaSearch {
+    #[serde(default)]
+    pub q: String,
+}
+
+#[derive(Serialize)]
+pub struct Uploaded {
+    pub url: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/media/list.html")]
+pub struct MediaListTemplate {
+    pub page: Page<Media>,
+    pub q: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/media/edit.html")]
+pub struct EditMediaTemplate {
+    pub media: Media,
+    pub posts: Vec<Post>,
+}
+
+pub async fn all(
+    Query(search): Query<MediaSearch>,
+    Query(pagination): Query<Pagination>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let page = Media::page(&state.db, search.q.trim(), pagination)
+        .await?
+        .with_filter("q", &search.q);
+    Ok(HtmlTemplate(MediaListTemplate { page, q: search.q }))
+}
+
+pub async fn create(
+    State(state): State<Arc<HistoryState>>,
+    TypedMultipart(upload): TypedMultipart<MediaUpload>,
+) -> Result<impl IntoResponse, HistoryError> {
+    save(&state, upload).await?;
+    Ok(Redirect::to("/media"))
+}
+
+// Called by summernote when an image is dropped or pasted into the editor,
+// so that it lands in the library instead of the post body as base64.
+pub async fn upload(
+    State(state): State<Arc<HistoryState>>,
+    TypedMultipart(upload): TypedMultipart<MediaUpload>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let media = save(&state, upload).await?;
+    Ok(Json(Uploaded { url: media.url() }))
+}
+
+pub async fn edit(
+    Path(id): Path<u32>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let media = Media::fetch(&state.db, id).await?;
+    let posts = Post::referencing(&state.db, &media.file_name).await?;
+    Ok(HtmlTemplate(EditMediaTemplate { media, posts }))
+}
+
+pub async fn update(
+    Path(id): Path<u32>,
+    State(state): State<Arc<HistoryState>>,
+    Form(description): Form<MediaDescription>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Media::update(&state.db, id, description).await?;
+    Ok(Redirect::to("/media"))
+}
+
+pub async fn delete(
+    Path(id): Path<u32>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let media = Media::fetch(&state.db, id).await?;
+    if media.uses > 0 {
+        return Err(HistoryError::Invalid(format!(
+            "Файл используется в материалах сайта ({}), сначала уберите его оттуда",
+            media.uses
+        )));
+    }
+    Media::delete(&state.db, id).await?;
+    let removed = if media.has_variants() {
+        images::remove(&state.storage, &media.file_name)
+    } else {
+        state
+            .storage
+            .remove(&media.file_name)
+            .map_err(HistoryError::from)
+    };
+    if removed.is_err() {
+        println!("Can't remove media file {}", media.file_name);
+    }
+    Ok(Redirect::to("/media"))
+}
+
+async fn save(state: &HistoryState, upload: MediaUpload) -> Result<Media, HistoryError> {
+    let storage = state.storage.clone();
+    let contents = upload.file.contents;
+    let size = contents.len() as i64;
+    let (file_name, mime) = tokio::task::spawn_blocking(move || store(&storage, &contents))
+        .await
+        .map_err(|_| HistoryError::InternalError)??;
+    let new_media = NewMedia {
+        file_name,
+        original_name: upload.file.metadata.file_name.unwrap_or_default(),
+        mime: mime.to_string(),
+        size,
+        alt: upload.alt.unwrap_or_default(),
+        caption: upload.caption.unwrap_or_default(),
+    };
+    Ok(Media::create(&state.db, new_media).await?)
+}
+
+// Images go through the same pipeline as covers so they lose their EXIF
+// data and get thumbnails for the library grid.
+fn store(storage: &Storage, bytes: &[u8]) -> Result<(String, &'static str), HistoryError> {
+    let accepted = storage.accept(bytes)?;
+    let file_name = if accepted.kind.is_image() {
+        images::process(storage, bytes, &accepted.hash)?
+    } else {
+        storage.store(bytes)?
+    };
+    Ok((file_name, accepted.kind.mime))
+}
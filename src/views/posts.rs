@@ -1,55 +1,301 @@
 use askama::Template;
 use axum::{
     body::Bytes,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::{IntoResponse, Redirect},
 };
+use axum_extra::extract::Form;
 use axum_typed_multipart::{FieldData, TryFromMultipart, TypedMultipart};
-use chrono::Local;
-use std::{
-    fs::{remove_file, File},
-    io::prelude::*,
-    sync::Arc,
-};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::sync::Arc;
 
 use crate::{
-    models::post::{NewPost, Post},
-    HistoryError, HistoryState, HtmlTemplate, IMG_PATH,
+    images,
+    markup::{self, footnotes::Note, Format, Rendered},
+    models::{
+        media::Media,
+        post::{NewPost, Post},
+        source::{NewSource, Source},
+        tag::{self, Tag},
+        term::{Entry, Term},
+        Page, Pagination,
+    },
+    slug,
+    storage::Storage,
+    views::{
+        bulk::{Action, Bulk, Choice},
+        terms::TermChoice,
+    },
+    HistoryError, HistoryState, HtmlTemplate,
 };
 
 #[derive(TryFromMultipart)]
 pub struct PostWithImage {
     pub title: String,
+    #[form_data(default)]
+    pub slug: String,
     pub lead: String,
     pub body: String,
-    pub cover: FieldData<Bytes>,
+    pub format: String,
+    pub cover: Option<FieldData<Bytes>>,
+    #[form_data(default)]
+    pub remove_cover: bool,
+    // Comma-separated names
+    #[form_data(default)]
+    pub tags: String,
+    // Ids of the checked terms
+    pub terms: Vec<u32>,
+    // From a datetime-local input, in the site offset; empty keeps the current date
+    #[form_data(default)]
+    pub published_at: String,
+    #[form_data(default)]
+    pub draft: bool,
+    pub source_author: Vec<String>,
+    pub source_title: Vec<String>,
+    pub source_year: Vec<String>,
+    pub source_archive: Vec<String>,
+    pub source_fond: Vec<String>,
+    pub source_opis: Vec<String>,
+    pub source_delo: Vec<String>,
+    pub source_url: Vec<String>,
+}
+
+pub enum CoverChange {
+    Keep,
+    Replace(FieldData<Bytes>),
+    Remove,
+}
+
+impl PostWithImage {
+    // Browsers submit an empty, nameless part for a file input that was left
+    // alone, which means "keep"; a new file wins over the remove checkbox.
+    fn cover_change(&mut self) -> CoverChange {
+        match self.cover.take() {
+            Some(cover) if !cover.contents.is_empty() => CoverChange::Replace(cover),
+            _ if self.remove_cover => CoverChange::Remove,
+            _ => CoverChange::Keep,
+        }
+    }
+
+    fn validate(&self) -> Result<(), HistoryError> {
+        check_body(Format::from(self.format.as_str()), &self.body)
+    }
+
+    fn published_at(&self, offset: FixedOffset) -> Result<Option<DateTime<Utc>>, HistoryError> {
+        parse_published_at(&self.published_at, offset)
+    }
+
+    async fn slug(&self, db: &SqlitePool, id: u32) -> Result<String, HistoryError> {
+        choose_slug(db, &self.slug, &self.title, id).await
+    }
+
+    // Every source row in the form submits all of its inputs, so the lists
+    // line up by index; rows left completely blank are dropped.
+    fn sources(&mut self) -> Vec<NewSource> {
+        let mut columns = [
+            &mut self.source_author,
+            &mut self.source_title,
+            &mut self.source_year,
+            &mut self.source_archive,
+            &mut self.source_fond,
+            &mut self.source_opis,
+            &mut self.source_delo,
+            &mut self.source_url,
+        ]
+        .map(|column| std::mem::take(column).into_iter());
+        let rows = columns.iter().map(|column| column.len()).max().unwrap_or(0);
+        (0..rows)
+            .map(|_| {
+                let [author, title, year, archive, fond, opis, delo, url] = columns
+                    .each_mut()
+                    .map(|column| column.next().unwrap_or_default().trim().to_string());
+                NewSource {
+                    author,
+                    title,
+                    year,
+                    archive,
+                    fond,
+                    opis,
+                    delo,
+                    url,
+                }
+            })
+            .filter(|source| !source.is_empty())
+            .collect()
+    }
+}
+
+pub fn check_body(format: Format, body: &str) -> Result<(), HistoryError> {
+    let problems = markup::check(format, body);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(HistoryError::Invalid(problems.join("\n")))
+    }
+}
+
+// Source links end up in an href on the entry page
+pub fn check_sources(sources: &[NewSource]) -> Result<(), HistoryError> {
+    for source in sources {
+        let url = source.url.trim();
+        if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(HistoryError::Invalid(format!(
+                "Ссылка на источник должна начинаться с http:// или https://: {}",
+                url
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub fn parse_published_at(
+    input: &str,
+    offset: FixedOffset,
+) -> Result<Option<DateTime<Utc>>, HistoryError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    ["%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .and_then(|at| offset.from_local_datetime(&at).single())
+        .map(|at| Some(at.with_timezone(&Utc)))
+        .ok_or_else(|| HistoryError::Invalid(format!("Неверная дата публикации: {}", input)))
+}
+
+// A slug typed in by hand must be free; one made from the title gets a
+// number appended until it is.
+pub async fn choose_slug(
+    db: &SqlitePool,
+    typed: &str,
+    title: &str,
+    id: u32,
+) -> Result<String, HistoryError> {
+    let typed = typed.trim();
+    if !typed.is_empty() {
+        if !slug::is_valid(typed) {
+            return Err(HistoryError::Invalid(
+                "Адрес может состоять только из латинских букв, цифр и дефисов".to_string(),
+            ));
+        }
+        if Post::slug_taken(db, typed, id).await? {
+            return Err(HistoryError::Invalid(format!("Адрес {} уже занят", typed)));
+        }
+        return Ok(typed.to_string());
+    }
+    let mut base = slug::slugify(title);
+    if !slug::is_valid(&base) {
+        base = format!("post-{}", base).trim_end_matches('-').to_string();
+    }
+    let mut candidate = base.clone();
+    let mut number = 2;
+    while Post::slug_taken(db, &candidate, id).await? {
+        candidate = format!("{}-{}", base, number);
+        number += 1;
+    }
+    Ok(candidate)
+}
+
+#[derive(Deserialize)]
+pub struct PreviewInput {
+    pub format: Format,
+    pub body: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/posts/preview.html")]
+pub struct PreviewTemplate {
+    pub body: String,
+    pub notes: Vec<Note>,
+    pub problems: Vec<String>,
 }
 
 #[derive(Template)]
 #[template(path = "admin/posts/list.html")]
 pub struct PostsTemplate {
-    pub posts: Vec<Post>,
+    pub page: Page<Post>,
+    pub bulk: Choice,
 }
 
 #[derive(Template)]
 #[template(path = "admin/posts/add.html")]
-pub struct NewPostTemplate;
+pub struct NewPostTemplate {
+    pub utc_offset: String,
+    pub all_tags: Vec<Tag>,
+    pub terms: TermChoice,
+}
 
 #[derive(Template)]
 #[template(path = "admin/posts/edit.html")]
 pub struct EditPostTemplate {
     pub post: Post,
+    // In the site offset, with seconds, so saving the form unchanged keeps the date
+    pub published_at: String,
+    pub utc_offset: String,
+    pub sources: Vec<Source>,
+    pub tags: String,
+    pub all_tags: Vec<Tag>,
+    pub terms: TermChoice,
 }
 
 pub async fn all(
+    Query(pagination): Query<Pagination>,
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    let posts = Post::list(&state.db).await?;
-    Ok(HtmlTemplate(PostsTemplate { posts }))
+    let page = Post::page(&state.db, pagination).await?;
+    Ok(HtmlTemplate(PostsTemplate {
+        page,
+        bulk: Choice::posts(),
+    }))
 }
 
-pub async fn add() -> impl IntoResponse {
-    HtmlTemplate(NewPostTemplate {})
+pub async fn bulk(
+    State(state): State<Arc<HistoryState>>,
+    Form(bulk): Form<Bulk>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let ids = bulk.ids()?;
+    match bulk.action {
+        Action::Delete => {
+            for post in Post::delete_many(&state.db, ids).await? {
+                if !Media::in_use(&state.db, &post.cover).await? {
+                    remove_cover(&state.storage, &post.cover);
+                }
+            }
+        }
+        Action::Publish => Post::set_draft(&state.db, ids, false).await?,
+        Action::Unpublish => Post::set_draft(&state.db, ids, true).await?,
+        Action::Tag | Action::Untag => {
+            let tags = tag::parse(&bulk.tags);
+            if tags.is_empty() {
+                return Err(HistoryError::Invalid("Укажите теги".to_string()));
+            }
+            match bulk.action {
+                Action::Tag => Tag::attach(&state.db, ids, tags).await?,
+                _ => Tag::detach(&state.db, ids, tags).await?,
+            }
+        }
+        Action::Move => {
+            return Err(HistoryError::Invalid(
+                "Публикации Школы нельзя перенести в другой раздел".to_string(),
+            ))
+        }
+    }
+    Ok(Redirect::to("/posts"))
+}
+
+pub async fn add(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let all_tags = Tag::list(&state.db).await?;
+    let terms = TermChoice::load(&state.db, None).await?;
+    Ok(HtmlTemplate(NewPostTemplate {
+        utc_offset: state.site.utc_offset.clone(),
+        all_tags,
+        terms,
+    }))
 }
 
 pub async fn edit(
@@ -57,69 +303,507 @@ pub async fn edit(
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
     let post = Post::fetch(&state.db, id).await?;
-    Ok(HtmlTemplate(EditPostTemplate { post }))
+    let sources = Source::list(&state.db, id).await?;
+    let tags = Tag::for_post(&state.db, id)
+        .await?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let all_tags = Tag::list(&state.db).await?;
+    let terms = TermChoice::load(&state.db, Some((Entry::Post, id))).await?;
+    let published_at = post
+        .published_at
+        .with_timezone(&state.site.offset())
+        .format("%Y-%m-%dT%H:%M:%S")
+        .to_string();
+    Ok(HtmlTemplate(EditPostTemplate {
+        post,
+        published_at,
+        utc_offset: state.site.utc_offset.clone(),
+        sources,
+        tags,
+        all_tags,
+        terms,
+    }))
 }
 
 pub async fn create(
     State(state): State<Arc<HistoryState>>,
-    TypedMultipart(post_with_image): TypedMultipart<PostWithImage>,
+    TypedMultipart(mut post_with_image): TypedMultipart<PostWithImage>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    let file_name = Local::now().timestamp().to_string()
-        + "_"
-        + &post_with_image
-            .cover
-            .metadata
-            .file_name
-            .unwrap_or(String::new());
+    post_with_image.validate()?;
+    let slug = post_with_image.slug(&state.db, 0).await?;
+    let published_at = post_with_image.published_at(state.site.offset())?;
+    let sources = post_with_image.sources();
+    let tags = tag::parse(&post_with_image.tags);
+    let file_name = match post_with_image.cover_change() {
+        CoverChange::Replace(cover) => store_cover(&state.storage, cover).await?,
+        CoverChange::Keep | CoverChange::Remove => String::new(),
+    };
     let new_post = NewPost {
         title: post_with_image.title,
         lead: post_with_image.lead,
         body: post_with_image.body,
-        cover: file_name,
+        format: Format::from(post_with_image.format.as_str()),
+        cover: file_name.clone(),
+        slug,
+        published_at,
+        draft: Some(post_with_image.draft),
     };
-    let mut file = File::create(format!("{}/{}", IMG_PATH, new_post.cover))?;
-    file.write_all(&post_with_image.cover.contents)?;
-    Post::create(&state.db, new_post).await?;
+    let terms = post_with_image.terms;
+    let saved: Result<(), sqlx::Error> = async {
+        let mut tx = state.db.begin().await?;
+        let id = Post::create_in(&mut tx, new_post)
+            .await?
+            .last_insert_rowid() as u32;
+        Source::replace_in(&mut tx, id, sources).await?;
+        Tag::replace_in(&mut tx, id, tags).await?;
+        Term::replace_in(&mut tx, Entry::Post, id, &terms).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(error) = saved {
+        if !file_name.is_empty() && !Media::in_use(&state.db, &file_name).await? {
+            remove_cover(&state.storage, &file_name);
+        }
+        return Err(error.into());
+    }
     Ok(Redirect::to("/posts"))
 }
 
 pub async fn update(
     Path(id): Path<u32>,
     State(state): State<Arc<HistoryState>>,
-    TypedMultipart(post_with_image): TypedMultipart<PostWithImage>,
+    TypedMultipart(mut post_with_image): TypedMultipart<PostWithImage>,
 ) -> Result<impl IntoResponse, HistoryError> {
+    post_with_image.validate()?;
+    let slug = post_with_image.slug(&state.db, id).await?;
+    let published_at = post_with_image.published_at(state.site.offset())?;
+    let sources = post_with_image.sources();
+    let tags = tag::parse(&post_with_image.tags);
     let old_post = Post::fetch(&state.db, id).await?;
-    let file_name = Local::now().timestamp().to_string()
-        + "_"
-        + &post_with_image
-            .cover
-            .metadata
-            .file_name
-            .unwrap_or(String::new());
-    let new_cover = if file_name.eq(&"".to_string()) {
-        old_post.cover
-    } else {
-        let mut file = File::create(format!("{}/{}", IMG_PATH, file_name))?;
-        file.write_all(&post_with_image.cover.contents)?;
-        remove_file(format!("{}/{}", IMG_PATH, old_post.cover))?;
-        file_name
+    // The new file is written before the post points at it and the old one
+    // is removed only after the post no longer does, so a failure at any
+    // step leaves the post with a cover that exists.
+    let new_cover = match post_with_image.cover_change() {
+        CoverChange::Keep => old_post.cover.clone(),
+        CoverChange::Replace(cover) => store_cover(&state.storage, cover).await?,
+        CoverChange::Remove => String::new(),
     };
     let updated_post = NewPost {
         title: post_with_image.title,
         lead: post_with_image.lead,
         body: post_with_image.body,
-        cover: new_cover,
+        format: Format::from(post_with_image.format.as_str()),
+        cover: new_cover.clone(),
+        slug,
+        published_at,
+        draft: Some(post_with_image.draft),
     };
-    Post::update(&state.db, id, updated_post).await?;
+    let terms = post_with_image.terms;
+    let saved: Result<(), sqlx::Error> = async {
+        let mut tx = state.db.begin().await?;
+        Post::update_in(&mut tx, id, updated_post).await?;
+        Source::replace_in(&mut tx, id, sources).await?;
+        Tag::replace_in(&mut tx, id, tags).await?;
+        Term::replace_in(&mut tx, Entry::Post, id, &terms).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(error) = saved {
+        if new_cover != old_post.cover && !Media::in_use(&state.db, &new_cover).await? {
+            remove_cover(&state.storage, &new_cover);
+        }
+        return Err(error.into());
+    }
+    if new_cover != old_post.cover && !Media::in_use(&state.db, &old_post.cover).await? {
+        remove_cover(&state.storage, &old_post.cover);
+    }
     Ok(Redirect::to("/posts"))
 }
 
+pub async fn preview(Form(input): Form<PreviewInput>) -> impl IntoResponse {
+    let problems = markup::check(input.format, &input.body);
+    let Rendered { body, notes } = markup::render(input.format, &input.body);
+    HtmlTemplate(PreviewTemplate {
+        body,
+        notes,
+        problems,
+    })
+}
+
 pub async fn delete(
     Path(id): Path<u32>,
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
     let post = Post::fetch(&state.db, id).await?;
     Post::delete(&state.db, id).await?;
-    remove_file(format!("{}/{}", IMG_PATH, post.cover))?;
+    if !Media::in_use(&state.db, &post.cover).await? {
+        remove_cover(&state.storage, &post.cover);
+    }
     Ok(Redirect::to("/posts"))
 }
+
+async fn store_cover(storage: &Storage, cover: FieldData<Bytes>) -> Result<String, HistoryError> {
+    let storage = storage.clone();
+    tokio::task::spawn_blocking(move || {
+        let accepted = storage.accept(&cover.contents)?;
+        images::process(&storage, &cover.contents, &accepted.hash)
+    })
+    .await
+    .map_err(|_| HistoryError::InternalError)?
+}
+
+// The post is already saved at this point, a file that can't be removed is
+// left for `check-uploads` rather than failing the request.
+pub fn remove_cover(storage: &Storage, cover: &str) {
+    if cover.is_empty() {
+        return;
+    }
+    if images::remove(storage, cover).is_err() {
+        println!("Can't remove cover {}", cover);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analytics::Analytics,
+        config::{Backup, Site},
+        models::{media::NewMedia, MIGRATOR},
+    };
+    use axum::http::HeaderMap;
+    use axum_typed_multipart::FieldMetadata;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use sqlx::{query, sqlite::SqlitePoolOptions};
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    async fn state() -> (Arc<HistoryState>, TempDir) {
+        let uploads = TempDir::new().unwrap();
+        // Every connection to :memory: is a separate database
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        MIGRATOR.run(&db).await.unwrap();
+        let state = HistoryState {
+            db,
+            storage: Storage::new(uploads.path()),
+            site: Site::default(),
+            backup: Backup::default(),
+            analytics: Analytics::default(),
+        };
+        (Arc::new(state), uploads)
+    }
+
+    fn png(shade: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        RgbImage::from_pixel(64, 48, Rgb([shade, 100, 50]))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn file(file_name: &str, contents: Vec<u8>) -> FieldData<Bytes> {
+        FieldData {
+            metadata: FieldMetadata {
+                name: Some("cover".to_string()),
+                file_name: Some(file_name.to_string()),
+                content_type: Some("image/png".to_string()),
+                headers: HeaderMap::new(),
+            },
+            contents: Bytes::from(contents),
+        }
+    }
+
+    fn form(cover: Option<FieldData<Bytes>>, remove_cover: bool) -> PostWithImage {
+        PostWithImage {
+            title: "Заголовок".to_string(),
+            slug: String::new(),
+            lead: "Лид".to_string(),
+            body: "<p>Текст</p>".to_string(),
+            format: "html".to_string(),
+            cover,
+            remove_cover,
+            tags: String::new(),
+            source_author: vec![],
+            source_title: vec![],
+            source_year: vec![],
+            source_archive: vec![],
+            source_fond: vec![],
+            source_opis: vec![],
+            source_delo: vec![],
+            source_url: vec![],
+            terms: vec![],
+            published_at: String::new(),
+            draft: false,
+        }
+    }
+
+    async fn create_post(state: &Arc<HistoryState>, cover: Option<FieldData<Bytes>>) -> Post {
+        assert!(
+            create(State(state.clone()), TypedMultipart(form(cover, false)))
+                .await
+                .is_ok()
+        );
+        Post::list(&state.db).await.unwrap().remove(0)
+    }
+
+    async fn update_post(state: &Arc<HistoryState>, id: u32, post_with_image: PostWithImage) {
+        assert!(update(
+            Path(id),
+            State(state.clone()),
+            TypedMultipart(post_with_image)
+        )
+        .await
+        .is_ok());
+    }
+
+    fn stored(state: &HistoryState, cover: &str) -> bool {
+        state.storage.exists(cover)
+            && state
+                .storage
+                .exists(&images::variant_name(cover, "og", "jpg"))
+    }
+
+    #[tokio::test]
+    async fn keeps_cover_when_no_file_is_sent() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        update_post(&state, post.id, form(None, false)).await;
+        let updated = Post::fetch(&state.db, post.id).await.unwrap();
+        assert_eq!(updated.cover, post.cover);
+        assert!(stored(&state, &updated.cover));
+    }
+
+    #[tokio::test]
+    async fn keeps_cover_when_file_input_is_left_empty() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        update_post(&state, post.id, form(Some(file("", vec![])), false)).await;
+        let updated = Post::fetch(&state.db, post.id).await.unwrap();
+        assert_eq!(updated.cover, post.cover);
+        assert!(stored(&state, &updated.cover));
+    }
+
+    #[tokio::test]
+    async fn replaces_cover_and_removes_old_files() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        update_post(&state, post.id, form(Some(file("b.png", png(200))), false)).await;
+        let updated = Post::fetch(&state.db, post.id).await.unwrap();
+        assert_ne!(updated.cover, post.cover);
+        assert!(stored(&state, &updated.cover));
+        assert!(!state.storage.exists(&post.cover));
+        assert!(!state
+            .storage
+            .exists(&images::variant_name(&post.cover, "thumb", "webp")));
+    }
+
+    #[tokio::test]
+    async fn new_file_wins_over_remove_checkbox() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        update_post(&state, post.id, form(Some(file("b.png", png(200))), true)).await;
+        let updated = Post::fetch(&state.db, post.id).await.unwrap();
+        assert!(!updated.cover.is_empty());
+        assert_ne!(updated.cover, post.cover);
+        assert!(stored(&state, &updated.cover));
+    }
+
+    #[tokio::test]
+    async fn removes_cover() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        update_post(&state, post.id, form(None, true)).await;
+        let updated = Post::fetch(&state.db, post.id).await.unwrap();
+        assert!(updated.cover.is_empty());
+        assert!(!state.storage.exists(&post.cover));
+    }
+
+    #[tokio::test]
+    async fn keeps_old_cover_shared_with_another_post() {
+        let (state, _uploads) = state().await;
+        let first = create_post(&state, Some(file("a.png", png(10)))).await;
+        let second = create_post(&state, Some(file("copy.png", png(10)))).await;
+        assert_eq!(first.cover, second.cover);
+        update_post(&state, first.id, form(Some(file("b.png", png(200))), false)).await;
+        assert!(stored(&state, &second.cover));
+    }
+
+    #[tokio::test]
+    async fn rejected_replacement_keeps_cover() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        let result = update(
+            Path(post.id),
+            State(state.clone()),
+            TypedMultipart(form(Some(file("b.txt", b"not an image".to_vec())), false)),
+        )
+        .await;
+        assert!(result.is_err());
+        let updated = Post::fetch(&state.db, post.id).await.unwrap();
+        assert_eq!(updated.cover, post.cover);
+        assert!(stored(&state, &updated.cover));
+    }
+
+    #[tokio::test]
+    async fn creates_post_without_cover() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("", vec![]))).await;
+        assert!(post.cover.is_empty());
+    }
+
+    #[tokio::test]
+    async fn makes_unique_slugs_from_title() {
+        let (state, _uploads) = state().await;
+        create_post(&state, None).await;
+        create_post(&state, None).await;
+        assert!(Post::fetch_by_slug(&state.db, "zagolovok").await.is_ok());
+        assert!(Post::fetch_by_slug(&state.db, "zagolovok-2").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_taken_slug() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, None).await;
+        let mut post_with_image = form(None, false);
+        post_with_image.slug = post.slug.clone();
+        let result = create(State(state.clone()), TypedMultipart(post_with_image)).await;
+        assert!(result.is_err());
+    }
+
+    async fn add_to_library(state: &HistoryState, file_name: &str) {
+        let new_media = NewMedia {
+            file_name: file_name.to_string(),
+            original_name: "a.png".to_string(),
+            mime: "image/png".to_string(),
+            size: 0,
+            alt: String::new(),
+            caption: String::new(),
+        };
+        assert!(Media::create(&state.db, new_media).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn keeps_old_cover_that_is_in_the_media_library() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        add_to_library(&state, &post.cover).await;
+        update_post(&state, post.id, form(Some(file("b.png", png(200))), false)).await;
+        assert!(stored(&state, &post.cover));
+    }
+
+    #[tokio::test]
+    async fn keeps_removed_cover_shown_in_another_post() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        let mut other = form(None, false);
+        other.body = format!("<p><img src=\"/static/uploads/{}\"></p>", post.cover);
+        assert!(create(State(state.clone()), TypedMultipart(other))
+            .await
+            .is_ok());
+        update_post(&state, post.id, form(None, true)).await;
+        assert!(stored(&state, &post.cover));
+    }
+
+    #[tokio::test]
+    async fn bulk_delete_keeps_cover_that_is_in_the_media_library() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, Some(file("a.png", png(10)))).await;
+        add_to_library(&state, &post.cover).await;
+        let delete = Bulk {
+            ids: vec![post.id],
+            action: Action::Delete,
+            tags: String::new(),
+            target: None,
+        };
+        assert!(bulk(State(state.clone()), Form(delete)).await.is_ok());
+        assert!(Post::fetch(&state.db, post.id).await.is_err());
+        assert!(stored(&state, &post.cover));
+    }
+
+    #[tokio::test]
+    async fn publication_date_is_typed_in_the_site_offset_and_kept_on_save() {
+        let (state, _uploads) = state().await;
+        let mut new = form(None, false);
+        new.published_at = "2024-05-01T12:30:45".to_string();
+        assert!(create(State(state.clone()), TypedMultipart(new))
+            .await
+            .is_ok());
+        let post = Post::list(&state.db).await.unwrap().remove(0);
+        assert_eq!(
+            post.published_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "2024-05-01 09:30:45"
+        );
+        let page = edit(Path(post.id), State(state.clone()))
+            .await
+            .ok()
+            .unwrap()
+            .into_response();
+        let body = hyper::body::to_bytes(page.into_body()).await.unwrap();
+        let shown = "value=\"2024-05-01T12:30:45\"";
+        assert!(String::from_utf8_lossy(&body).contains(shown));
+        let mut unchanged = form(None, false);
+        unchanged.published_at = "2024-05-01T12:30:45".to_string();
+        update_post(&state, post.id, unchanged).await;
+        let updated = Post::fetch(&state.db, post.id).await.unwrap();
+        assert_eq!(updated.published_at, post.published_at);
+    }
+
+    async fn fail_tags(state: &HistoryState) {
+        query("CREATE TRIGGER fail_tags BEFORE INSERT ON post_tags BEGIN SELECT RAISE(ABORT, 'fail'); END")
+            .execute(&state.db)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_create_saves_nothing_and_removes_the_cover() {
+        let (state, uploads) = state().await;
+        fail_tags(&state).await;
+        let mut new = form(Some(file("a.png", png(10))), false);
+        new.tags = "тег".to_string();
+        assert!(create(State(state.clone()), TypedMultipart(new))
+            .await
+            .is_err());
+        assert!(Post::list(&state.db).await.unwrap().is_empty());
+        assert_eq!(std::fs::read_dir(uploads.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn failed_update_leaves_the_post_as_it_was() {
+        let (state, _uploads) = state().await;
+        let post = create_post(&state, None).await;
+        fail_tags(&state).await;
+        let mut changed = form(None, false);
+        changed.title = "Другой заголовок".to_string();
+        changed.tags = "тег".to_string();
+        changed.source_author = vec!["Автор".to_string()];
+        changed.source_title = vec!["Источник".to_string()];
+        for column in [
+            &mut changed.source_year,
+            &mut changed.source_archive,
+            &mut changed.source_fond,
+            &mut changed.source_opis,
+            &mut changed.source_delo,
+            &mut changed.source_url,
+        ] {
+            column.push(String::new());
+        }
+        assert!(
+            update(Path(post.id), State(state.clone()), TypedMultipart(changed))
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            Post::fetch(&state.db, post.id).await.unwrap().title,
+            post.title
+        );
+        assert!(Source::list(&state.db, post.id).await.unwrap().is_empty());
+    }
+}
@@ -1,47 +1,112 @@
 use askama::Template;
 use axum::{
-    extract::{Form, Path, State},
+    extract::{Path, Query, State},
     response::{IntoResponse, Redirect},
+    Json,
 };
+use axum_extra::extract::Form;
+use serde::Deserialize;
 use std::sync::Arc;
 
 use crate::{
-    models::publication::{NewPublication, Publication},
-    HistoryError, HistoryState, HtmlTemplate,
+    models::{
+        publication::{NewPublication, Publication},
+        section::Section,
+        term::{Entry, Term},
+        Page, Pagination,
+    },
+    views::bulk::{self, Bulk, Choice},
+    views::check_link,
+    views::terms::TermChoice,
+    HistoryError, HistoryState, HtmlTemplate, Ordering,
 };
 
 #[derive(Template)]
 #[template(path = "admin/publications/list.html")]
 pub struct PublicationsTemplate {
-    pub publications: Vec<Publication>,
+    pub page: Page<Publication>,
+    pub order: String,
+    pub bulk: Choice,
 }
 
 #[derive(Template)]
 #[template(path = "admin/publications/add.html")]
-pub struct NewPublicationTemplate;
+pub struct NewPublicationTemplate {
+    pub terms: TermChoice,
+}
 
 #[derive(Template)]
 #[template(path = "admin/publications/edit.html")]
 pub struct EditPublicationTemplate {
     pub publication: Publication,
+    pub terms: TermChoice,
+}
+
+// The form also carries the ids of the checked terms, hence the form
+// extractor that collects repeated fields.
+#[derive(Deserialize)]
+pub struct PublicationForm {
+    pub name: String,
+    pub link: String,
+    pub description: String,
+    #[serde(default)]
+    pub terms: Vec<u32>,
+}
+
+impl PublicationForm {
+    fn split(self) -> Result<(NewPublication, Vec<u32>), HistoryError> {
+        let publication = NewPublication {
+            name: self.name,
+            link: check_link(self.link)?,
+            description: self.description,
+        };
+        Ok((publication, self.terms))
+    }
 }
 
 pub async fn all(
+    Query(pagination): Query<Pagination>,
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    let publications = Publication::list(&state.db).await?;
-    Ok(HtmlTemplate(PublicationsTemplate { publications }))
+    let page = Publication::page(&state.db, pagination).await?;
+    let order = Ordering::attribute(&Publication::ids(&state.db).await?);
+    Ok(HtmlTemplate(PublicationsTemplate {
+        page,
+        order,
+        bulk: Choice::catalog(Section::Publications),
+    }))
 }
 
-pub async fn add() -> impl IntoResponse {
-    HtmlTemplate(NewPublicationTemplate {})
+pub async fn bulk(
+    State(state): State<Arc<HistoryState>>,
+    Form(bulk): Form<Bulk>,
+) -> Result<impl IntoResponse, HistoryError> {
+    bulk::catalog(&state.db, Section::Publications, bulk).await
+}
+
+pub async fn reorder(
+    State(state): State<Arc<HistoryState>>,
+    Json(ordering): Json<Ordering>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Ordering::saved(Publication::reorder(&state.db, &ordering.ids).await?)
+}
+
+pub async fn add(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let terms = TermChoice::load(&state.db, None).await?;
+    Ok(HtmlTemplate(NewPublicationTemplate { terms }))
 }
 
 pub async fn create(
     State(state): State<Arc<HistoryState>>,
-    Form(new_publication): Form<NewPublication>,
+    Form(form): Form<PublicationForm>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    Publication::create(&state.db, new_publication).await?;
+    let (new_publication, terms) = form.split()?;
+    let id = Publication::create(&state.db, new_publication)
+        .await?
+        .last_insert_rowid() as u32;
+    Term::replace(&state.db, Entry::Publication, id, &terms).await?;
     Ok(Redirect::to("/publications"))
 }
 
@@ -50,15 +115,18 @@ pub async fn edit(
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
     let publication = Publication::fetch(&state.db, id).await?;
-    Ok(HtmlTemplate(EditPublicationTemplate { publication }))
+    let terms = TermChoice::load(&state.db, Some((Entry::Publication, id))).await?;
+    Ok(HtmlTemplate(EditPublicationTemplate { publication, terms }))
 }
 
 pub async fn update(
     Path(id): Path<u32>,
     State(state): State<Arc<HistoryState>>,
-    Form(updated_publication): Form<NewPublication>,
+    Form(form): Form<PublicationForm>,
 ) -> Result<impl IntoResponse, HistoryError> {
+    let (updated_publication, terms) = form.split()?;
     Publication::update(&state.db, id, updated_publication).await?;
+    Term::replace(&state.db, Entry::Publication, id, &terms).await?;
     Ok(Redirect::to("/publications"))
 }
 
@@ -0,0 +1,127 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use chrono::{Days, Utc};
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    models::{
+        overview::{self, Item},
+        visit::{self, Day, Kind, Total},
+    },
+    HistoryError, HistoryState, HtmlTemplate,
+};
+
+const TOP: u32 = 20;
+const PERIODS: [u64; 4] = [7, 30, 90, 365];
+const CHART_HEIGHT: i64 = 100;
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Period {
+    pub days: u64,
+}
+
+impl Default for Period {
+    fn default() -> Self {
+        Self { days: 30 }
+    }
+}
+
+pub struct Bar {
+    pub day: String,
+    pub views: i64,
+    pub visitors: i64,
+    // In the units of the chart, which is CHART_HEIGHT high
+    pub views_height: i64,
+    pub visitors_height: i64,
+}
+
+// Bars of views per day with the visitors drawn over them
+pub struct Chart {
+    pub bars: Vec<Bar>,
+    pub max: i64,
+    pub views: i64,
+}
+
+impl Chart {
+    fn new(days: Vec<Day>) -> Self {
+        let max = days.iter().map(|day| day.views).max().unwrap_or(0);
+        let height = |value: i64| match max {
+            0 => 0,
+            max => value * CHART_HEIGHT / max,
+        };
+        Chart {
+            max,
+            views: days.iter().map(|day| day.views).sum(),
+            bars: days
+                .into_iter()
+                .map(|day| Bar {
+                    day: day.day.format("%d.%m.%Y").to_string(),
+                    views: day.views,
+                    visitors: day.visitors,
+                    views_height: height(day.views),
+                    visitors_height: height(day.visitors),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.bars.len() * 10
+    }
+
+    pub fn height(&self) -> i64 {
+        CHART_HEIGHT
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/stats.html")]
+pub struct StatsTemplate {
+    pub periods: [u64; 4],
+    pub days: u64,
+    pub pages: Chart,
+    pub downloads: Chart,
+    pub top_pages: Vec<Total>,
+    // With the catalog entry the link belongs to, if it still does
+    pub top_downloads: Vec<(Total, Option<Item>)>,
+}
+
+impl StatsTemplate {
+    pub fn is_current(&self, period: &u64) -> bool {
+        *period == self.days
+    }
+}
+
+pub async fn page(
+    Query(period): Query<Period>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let days = period.days.clamp(7, 365);
+    let since = Utc::now().date_naive() - Days::new(days - 1);
+    let mut entries: HashMap<String, Item> = overview::catalog_links(&state.db)
+        .await?
+        .into_iter()
+        .map(|(item, link)| (link, item))
+        .collect();
+    let top_downloads = visit::top(&state.db, Kind::Download, since, TOP)
+        .await?
+        .into_iter()
+        .map(|total| {
+            let item = entries.remove(&total.path);
+            (total, item)
+        })
+        .collect();
+    Ok(HtmlTemplate(StatsTemplate {
+        periods: PERIODS,
+        days,
+        pages: Chart::new(visit::daily(&state.db, Kind::Page, since).await?),
+        downloads: Chart::new(visit::daily(&state.db, Kind::Download, since).await?),
+        top_pages: visit::top(&state.db, Kind::Page, since, TOP).await?,
+        top_downloads,
+    }))
+}
@@ -0,0 +1,130 @@
+use askama::Template;
+use axum::{
+    extract::{Form, Path, State},
+    response::{IntoResponse, Redirect},
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+
+use crate::{
+    models::term::{Entry, Kind, Node, Term},
+    HistoryError, HistoryState, HtmlTemplate,
+};
+
+#[derive(Template)]
+#[template(path = "admin/terms.html")]
+pub struct TermsTemplate {
+    pub groups: Vec<(Kind, Vec<Node>)>,
+}
+
+#[derive(Deserialize)]
+pub struct NewTerm {
+    pub kind: Kind,
+    // Empty for a top-level term
+    pub parent_id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TermName {
+    pub name: String,
+}
+
+// Checkboxes for the terms of a post, book or publication
+pub struct TermChoice {
+    pub groups: Vec<(Kind, Vec<Node>)>,
+    pub selected: Vec<u32>,
+}
+
+impl TermChoice {
+    pub async fn load(db: &SqlitePool, entry: Option<(Entry, u32)>) -> Result<Self, HistoryError> {
+        let terms = Term::list(db).await?;
+        let selected = match entry {
+            Some((entry, id)) => Term::for_entry(db, entry, id)
+                .await?
+                .into_iter()
+                .map(|term| term.id)
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(TermChoice {
+            groups: groups(&terms),
+            selected,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.iter().all(|(_, nodes)| nodes.is_empty())
+    }
+
+    pub fn is_selected(&self, id: &u32) -> bool {
+        self.selected.contains(id)
+    }
+}
+
+fn groups(terms: &[Term]) -> Vec<(Kind, Vec<Node>)> {
+    Kind::ALL
+        .into_iter()
+        .map(|kind| (kind, Term::tree(terms, kind)))
+        .collect()
+}
+
+fn name(name: &str) -> Result<&str, HistoryError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(HistoryError::Invalid("Укажите название".to_string()));
+    }
+    Ok(name)
+}
+
+pub async fn all(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let terms = Term::list(&state.db).await?;
+    Ok(HtmlTemplate(TermsTemplate {
+        groups: groups(&terms),
+    }))
+}
+
+pub async fn create(
+    State(state): State<Arc<HistoryState>>,
+    Form(new_term): Form<NewTerm>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let name = name(&new_term.name)?;
+    let parent_id = match new_term.parent_id.trim() {
+        "" => None,
+        id => {
+            let id = id
+                .parse()
+                .map_err(|_| HistoryError::Invalid("Неверный раздел".to_string()))?;
+            let parent = Term::fetch(&state.db, id).await?;
+            if parent.kind != new_term.kind {
+                return Err(HistoryError::Invalid(format!(
+                    "Раздел «{}» относится к другому классификатору",
+                    parent.name
+                )));
+            }
+            Some(id)
+        }
+    };
+    Term::create(&state.db, new_term.kind, parent_id, name).await?;
+    Ok(Redirect::to("/terms"))
+}
+
+pub async fn update(
+    Path(id): Path<u32>,
+    State(state): State<Arc<HistoryState>>,
+    Form(input): Form<TermName>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Term::rename(&state.db, id, name(&input.name)?).await?;
+    Ok(Redirect::to("/terms"))
+}
+
+pub async fn delete(
+    Path(id): Path<u32>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Term::delete(&state.db, id).await?;
+    Ok(Redirect::to("/terms"))
+}
@@ -1,19 +1,29 @@
 use askama::Template;
 use axum::{
-    extract::{Form, Path, State},
+    extract::{Path, Query, State},
     response::{IntoResponse, Redirect},
+    Json,
 };
+use axum_extra::extract::Form;
 use std::sync::Arc;
 
 use crate::{
-    models::text::{NewText, Text},
-    HistoryError, HistoryState, HtmlTemplate,
+    models::{
+        section::Section,
+        text::{NewText, Text},
+        Page, Pagination,
+    },
+    views::bulk::{self, Bulk, Choice},
+    views::check_link,
+    HistoryError, HistoryState, HtmlTemplate, Ordering,
 };
 
 #[derive(Template)]
 #[template(path = "admin/texts/list.html")]
 pub struct TextsTemplate {
-    pub texts: Vec<Text>,
+    pub page: Page<Text>,
+    pub order: String,
+    pub bulk: Choice,
 }
 
 #[derive(Template)]
@@ -27,10 +37,30 @@ pub struct EditTextTemplate {
 }
 
 pub async fn all(
+    Query(pagination): Query<Pagination>,
     State(state): State<Arc<HistoryState>>,
 ) -> Result<impl IntoResponse, HistoryError> {
-    let texts = Text::list(&state.db).await?;
-    Ok(HtmlTemplate(TextsTemplate { texts }))
+    let page = Text::page(&state.db, pagination).await?;
+    let order = Ordering::attribute(&Text::ids(&state.db).await?);
+    Ok(HtmlTemplate(TextsTemplate {
+        page,
+        order,
+        bulk: Choice::catalog(Section::Texts),
+    }))
+}
+
+pub async fn bulk(
+    State(state): State<Arc<HistoryState>>,
+    Form(bulk): Form<Bulk>,
+) -> Result<impl IntoResponse, HistoryError> {
+    bulk::catalog(&state.db, Section::Texts, bulk).await
+}
+
+pub async fn reorder(
+    State(state): State<Arc<HistoryState>>,
+    Json(ordering): Json<Ordering>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Ordering::saved(Text::reorder(&state.db, &ordering.ids).await?)
 }
 
 pub async fn add() -> impl IntoResponse {
@@ -39,8 +69,9 @@ pub async fn add() -> impl IntoResponse {
 
 pub async fn create(
     State(state): State<Arc<HistoryState>>,
-    Form(new_text): Form<NewText>,
+    Form(mut new_text): Form<NewText>,
 ) -> Result<impl IntoResponse, HistoryError> {
+    new_text.link = check_link(new_text.link)?;
     Text::create(&state.db, new_text).await?;
     Ok(Redirect::to("/texts"))
 }
@@ -56,8 +87,9 @@ pub async fn edit(
 pub async fn update(
     Path(id): Path<u32>,
     State(state): State<Arc<HistoryState>>,
-    Form(updated_text): Form<NewText>,
+    Form(mut updated_text): Form<NewText>,
 ) -> Result<impl IntoResponse, HistoryError> {
+    updated_text.link = check_link(updated_text.link)?;
     Text::update(&state.db, id, updated_text).await?;
     Ok(Redirect::to("/texts"))
 }
@@ -0,0 +1,161 @@
+use askama::Template;
+use axum::{
+    extract::{Form, Path, State},
+    response::{IntoResponse, Redirect},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::{
+    auth::{self, Scope},
+    models::token::Token,
+    Auth, HistoryError, HistoryState, HtmlTemplate,
+};
+
+#[derive(Template)]
+#[template(path = "admin/tokens.html")]
+pub struct TokensTemplate {
+    pub tokens: Vec<Token>,
+    pub scopes: [Scope; 2],
+    // The secret of a token that was just created, shown only this once
+    pub created: Option<String>,
+}
+
+// Checkboxes are only sent when checked
+#[derive(Deserialize)]
+pub struct TokenInput {
+    pub name: String,
+    pub posts: Option<String>,
+    pub catalog: Option<String>,
+    // Zero means the token never expires
+    pub expires_days: i64,
+}
+
+async fn page(
+    state: &HistoryState,
+    user_id: i64,
+    created: Option<String>,
+) -> Result<TokensTemplate, HistoryError> {
+    let tokens = Token::list(&state.db, user_id).await?;
+    Ok(TokensTemplate {
+        tokens,
+        scopes: Scope::ALL,
+        created,
+    })
+}
+
+// Far more than the form offers, and keeps the date within range
+const MAX_EXPIRES_DAYS: i64 = 3650;
+
+fn expires_at(days: i64) -> Result<Option<DateTime<Utc>>, HistoryError> {
+    if days <= 0 {
+        return Ok(None);
+    }
+    (days <= MAX_EXPIRES_DAYS)
+        .then(|| Utc::now().checked_add_signed(Duration::days(days)))
+        .flatten()
+        .map(Some)
+        .ok_or_else(|| {
+            HistoryError::Invalid(format!(
+                "Срок действия не может быть больше {} дней",
+                MAX_EXPIRES_DAYS
+            ))
+        })
+}
+
+fn user_id(auth: &Auth) -> Result<i64, HistoryError> {
+    auth.current_user
+        .as_ref()
+        .map(|user| user.id)
+        .ok_or(HistoryError::Unauthorized)
+}
+
+pub async fn all(
+    auth: Auth,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Ok(HtmlTemplate(page(&state, user_id(&auth)?, None).await?))
+}
+
+pub async fn create(
+    auth: Auth,
+    State(state): State<Arc<HistoryState>>,
+    Form(input): Form<TokenInput>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let user_id = user_id(&auth)?;
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(HistoryError::Invalid("Укажите название токена".to_string()));
+    }
+    let mut scopes = Vec::new();
+    if input.posts.is_some() {
+        scopes.push(Scope::Posts);
+    }
+    if input.catalog.is_some() {
+        scopes.push(Scope::Catalog);
+    }
+    if scopes.is_empty() {
+        return Err(HistoryError::Invalid(
+            "Выберите хотя бы один раздел".to_string(),
+        ));
+    }
+    let expires_at = expires_at(input.expires_days)?;
+    let secret = auth::generate_token();
+    Token::create(
+        &state.db,
+        user_id,
+        name,
+        &auth::hash_token(&secret),
+        &scopes,
+        expires_at,
+    )
+    .await?;
+    Ok(HtmlTemplate(page(&state, user_id, Some(secret)).await?))
+}
+
+pub async fn delete(
+    auth: Auth,
+    Path(id): Path<i64>,
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    Token::delete(&state.db, id, user_id(&auth)?).await?;
+    Ok(Redirect::to("/tokens"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{self, user::User};
+
+    #[test]
+    fn expiry_out_of_range_is_invalid() {
+        assert!(matches!(expires_at(0), Ok(None)));
+        assert!(matches!(expires_at(90), Ok(Some(_))));
+        assert!(matches!(
+            expires_at(MAX_EXPIRES_DAYS + 1),
+            Err(HistoryError::Invalid(_))
+        ));
+        assert!(matches!(
+            expires_at(i64::MAX),
+            Err(HistoryError::Invalid(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn user_cannot_delete_another_users_token() {
+        let db = models::memory().await;
+        for name in ["owner", "other"] {
+            User::create(&db, name, "hash").await.unwrap();
+        }
+        let owner = User::fetch_by_name(&db, "owner").await.unwrap();
+        let other = User::fetch_by_name(&db, "other").await.unwrap();
+        Token::create(&db, owner.id, "cli", "hash", &[Scope::Posts], None)
+            .await
+            .unwrap();
+        let token = Token::list(&db, owner.id).await.unwrap().remove(0);
+        let deleted = Token::delete(&db, token.id, other.id).await.unwrap();
+        assert_eq!(deleted.rows_affected(), 0);
+        assert_eq!(Token::list(&db, owner.id).await.unwrap().len(), 1);
+    }
+}
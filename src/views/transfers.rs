@@ -0,0 +1,82 @@
+use askama::Template;
+use axum::{body::Bytes, extract::State, http::header, response::IntoResponse};
+use axum_typed_multipart::{FieldData, TryFromMultipart, TypedMultipart};
+use chrono::Utc;
+use std::{io::Cursor, sync::Arc};
+
+use crate::{
+    transfer::{self, Export, Imported},
+    HistoryError, HistoryState, HtmlTemplate,
+};
+
+#[derive(TryFromMultipart)]
+pub struct ImportUpload {
+    pub archive: FieldData<Bytes>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/transfer.html")]
+pub struct TransferTemplate {
+    pub imported: Option<Imported>,
+}
+
+pub async fn page() -> impl IntoResponse {
+    HtmlTemplate(TransferTemplate { imported: None })
+}
+
+pub async fn export(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let export = transfer::export(&state.db).await?;
+    let storage = state.storage.clone();
+    let archive = tokio::task::spawn_blocking(move || {
+        let mut cursor = Cursor::new(Vec::new());
+        transfer::write_archive(&mut cursor, &export, &storage)?;
+        Ok::<_, HistoryError>(cursor.into_inner())
+    })
+    .await
+    .map_err(|_| HistoryError::InternalError)??;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"history-{}.zip\"",
+                    Utc::now().format("%Y-%m-%d")
+                ),
+            ),
+        ],
+        archive,
+    ))
+}
+
+pub async fn import(
+    State(state): State<Arc<HistoryState>>,
+    TypedMultipart(upload): TypedMultipart<ImportUpload>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let contents = upload.archive.contents;
+    let (export, uploads) = if transfer::is_archive(&contents) {
+        let storage = state.storage.clone();
+        let (export, uploads) = tokio::task::spawn_blocking(move || {
+            transfer::read_archive(Cursor::new(contents), &storage)
+        })
+        .await
+        .map_err(|_| HistoryError::InternalError)??;
+        (export, Some(uploads))
+    } else {
+        let export: Export = serde_json::from_slice(&contents)
+            .map_err(|_| HistoryError::Invalid("Файл не похож на выгрузку сайта".into()))?;
+        (export, None)
+    };
+    let imported = transfer::import(&state.db, export).await?;
+    if let Some(uploads) = uploads {
+        let storage = state.storage.clone();
+        tokio::task::spawn_blocking(move || uploads.restore(&storage))
+            .await
+            .map_err(|_| HistoryError::InternalError)??;
+    }
+    Ok(HtmlTemplate(TransferTemplate {
+        imported: Some(imported),
+    }))
+}
@@ -0,0 +1,32 @@
+use askama::Template;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+};
+use std::sync::Arc;
+
+use crate::{
+    consistency::{self, Report},
+    HistoryError, HistoryState, HtmlTemplate,
+};
+
+#[derive(Template)]
+#[template(path = "admin/uploads.html")]
+pub struct UploadsTemplate {
+    pub report: Report,
+}
+
+pub async fn check(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let report = consistency::check(&state.db, &state.storage).await?;
+    Ok(HtmlTemplate(UploadsTemplate { report }))
+}
+
+pub async fn cleanup(
+    State(state): State<Arc<HistoryState>>,
+) -> Result<impl IntoResponse, HistoryError> {
+    let report = consistency::check(&state.db, &state.storage).await?;
+    consistency::cleanup(&state.storage, &report).await?;
+    Ok(Redirect::to("/uploads"))
+}